@@ -0,0 +1,178 @@
+//! The lexer as it was before it borrowed from the source: the source is copied into a
+//! `Vec<char>`, every token owns its lexeme and the token vector is cloned at the end.
+//!
+//! Only kept around as a baseline for the `lexer` benchmark.
+
+// the fields only exist so that tokens cost as much as they used to
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub kind: u8,
+    pub lexeme: String,
+    pub literal: Option<String>,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+pub struct Lexer {
+    source: Vec<char>,
+    offsets: Vec<usize>,
+    tokens: Vec<Token>,
+    start: usize,
+    current: usize,
+    line: usize,
+}
+
+impl Lexer {
+    pub fn new(source: &str) -> Self {
+        let mut chars = vec![];
+        let mut offsets = vec![];
+        for (offset, ch) in source.char_indices() {
+            chars.push(ch);
+            offsets.push(offset);
+        }
+        offsets.push(source.len());
+        Self {
+            source: chars,
+            offsets,
+            tokens: Vec::new(),
+            start: 0,
+            current: 0,
+            line: 1,
+        }
+    }
+
+    pub fn scan_tokens(&mut self) -> Vec<Token> {
+        while !self.is_at_end() {
+            self.start = self.current;
+            self.scan_token();
+        }
+        self.start = self.current;
+        self.add_token(0, None);
+        self.tokens.clone()
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.current >= self.source.len()
+    }
+
+    fn scan_token(&mut self) {
+        let ch = self.advance();
+        match ch {
+            '(' | ')' | '{' | '}' | '[' | ']' | ',' | ';' | '^' | '~' | '?' => {
+                self.add_token(1, None)
+            }
+            '.' | ':' | '-' | '+' | '*' | '%' | '&' | '|' | '!' | '=' | '<' | '>' => {
+                if matches!(self.peek(), '.' | ':' | '>' | '=' | '&' | '|' | '<') {
+                    self.advance();
+                }
+                self.add_token(2, None);
+            }
+            '/' => {
+                if self.matches('/') {
+                    while self.peek() != '\n' && !self.is_at_end() {
+                        self.advance();
+                    }
+                } else if self.matches('*') {
+                    while !self.is_at_end() && !self.source[self.current..].starts_with(&['*', '/'])
+                    {
+                        if self.advance() == '\n' {
+                            self.line += 1;
+                        }
+                    }
+                    self.current += 2;
+                } else {
+                    self.add_token(2, None);
+                }
+            }
+            ' ' | '\r' | '\t' => {}
+            '\n' => self.line += 1,
+            '"' => self.string(),
+            _ => {
+                if ch.is_ascii_digit() {
+                    self.number();
+                } else if ch.is_ascii_alphanumeric() || ch == '_' {
+                    while self.peek().is_ascii_alphanumeric() || self.peek() == '_' {
+                        self.advance();
+                    }
+                    self.add_token(3, None);
+                }
+            }
+        }
+    }
+
+    fn advance(&mut self) -> char {
+        self.current += 1;
+        self.source.get(self.current - 1).copied().unwrap_or('\0')
+    }
+
+    fn peek(&self) -> char {
+        self.source.get(self.current).copied().unwrap_or('\0')
+    }
+
+    fn peek_next(&self) -> char {
+        self.source.get(self.current + 1).copied().unwrap_or('\0')
+    }
+
+    fn matches(&mut self, expected: char) -> bool {
+        if self.peek() == expected {
+            self.current += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn add_token(&mut self, kind: u8, literal: Option<String>) {
+        let end = self.current.min(self.source.len());
+        let mut lexeme = String::new();
+        for i in self.start..end {
+            lexeme.push(self.source[i]);
+        }
+        self.tokens.push(Token {
+            kind,
+            lexeme,
+            literal,
+            start: self.offsets[self.start],
+            end: self.offsets[end],
+            line: self.line,
+        });
+    }
+
+    fn string(&mut self) {
+        let mut literal = String::new();
+        while self.peek() != '"' && !self.is_at_end() {
+            match self.advance() {
+                '\\' => {
+                    let escaped = self.advance();
+                    literal.push(if escaped == 'n' { '\n' } else { escaped });
+                }
+                '\n' => {
+                    self.line += 1;
+                    literal.push('\n');
+                }
+                ch => literal.push(ch),
+            }
+        }
+        self.advance();
+        self.add_token(4, Some(literal));
+    }
+
+    fn number(&mut self) {
+        while self.peek().is_ascii_alphanumeric() || self.peek() == '_' {
+            self.advance();
+        }
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
+            self.advance();
+            while self.peek().is_ascii_digit() {
+                self.advance();
+            }
+        }
+        let mut literal = String::new();
+        for i in self.start..self.current {
+            literal.push(self.source[i]);
+        }
+        self.add_token(5, Some(literal));
+    }
+}
@@ -0,0 +1,61 @@
+//! Compares the borrowing, iterator-based lexer with the old `Vec<char>` one.
+//!
+//! Run with `cargo bench --bench lexer`.
+
+mod legacy;
+
+use blum::ast::lexer::Lexer;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const SNIPPET: &str = r#"
+/// Computes things.
+fn compute(a, b) {
+    let total = 0;
+    for (let i = 0; i < 1_000; i += 1) {
+        total += (a * i + b) % 7 << 2; // accumulate
+    }
+    /* a /* nested */ comment */
+    if (total >= 0x_ff && b != 2.5e3) {
+        let message = "total is \"large\"\n";
+    }
+    return total..=a;
+}
+"#;
+
+fn main() {
+    let source = SNIPPET.repeat(5_000);
+    println!(
+        "lexing {:.1} MiB of source",
+        source.len() as f64 / (1024.0 * 1024.0)
+    );
+
+    let legacy = bench("legacy Vec<char> lexer", || {
+        legacy::Lexer::new(&source).scan_tokens().len()
+    });
+    let current = bench("borrowing iterator lexer", || Lexer::new(&source).count());
+    let lossless = bench("borrowing iterator lexer, lossless", || {
+        Lexer::new(&source).lossless().count()
+    });
+
+    println!(
+        "speedup: {:.2}x ({:.2}x in lossless mode)",
+        legacy.as_secs_f64() / current.as_secs_f64(),
+        legacy.as_secs_f64() / lossless.as_secs_f64()
+    );
+}
+
+/// Runs `lex` a few times and reports the fastest run.
+fn bench(name: &str, mut lex: impl FnMut() -> usize) -> Duration {
+    const RUNS: usize = 10;
+
+    let mut best = Duration::MAX;
+    let mut tokens = 0;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        tokens = black_box(lex());
+        best = best.min(start.elapsed());
+    }
+    println!("{name:>36}: {best:>10.2?} for {tokens} tokens");
+    best
+}
@@ -0,0 +1,297 @@
+use super::{Expr, ForClauses, Param, Part, Stmt, Token};
+use std::borrow::Cow;
+
+/// Fills in the captures of every [`Expr::Lambda`] in a program: the variables declared
+/// in an enclosing function or closure that its body refers to. Variables of the top
+/// level are globals, which closures don't need to capture.
+pub fn analyze(statements: &mut [Stmt<'_>]) {
+    let mut analyzer = Analyzer {
+        scopes: vec![Vec::new()],
+        closures: Vec::new(),
+    };
+    analyzer.statements(statements);
+}
+
+struct Analyzer<'src> {
+    /// The names declared in each scope, innermost last. The first scope holds globals.
+    scopes: Vec<Vec<Cow<'src, str>>>,
+    /// For each closure being analyzed, the number of scopes outside of it and the
+    /// variables it captures so far.
+    closures: Vec<(usize, Vec<Token<'src>>)>,
+}
+
+impl<'src> Analyzer<'src> {
+    fn statements(&mut self, statements: &mut [Stmt<'src>]) {
+        for stmt in statements {
+            self.statement(stmt);
+        }
+    }
+
+    fn statement(&mut self, stmt: &mut Stmt<'src>) {
+        match stmt {
+            Stmt::Expression(expr, _) => self.expr(expr),
+            Stmt::Let {
+                pattern,
+                initializer,
+                ..
+            } => {
+                if let Some(initializer) = initializer {
+                    self.expr(initializer);
+                }
+                for name in pattern.bindings() {
+                    self.declare(name);
+                }
+            }
+            Stmt::Return(_, value, _) => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+                ..
+            } => {
+                self.expr(condition);
+                self.statement(body);
+                if let Some(increment) = increment {
+                    self.expr(increment);
+                }
+            }
+            Stmt::For { clauses, body, .. } => {
+                self.scopes.push(Vec::new());
+                match clauses.as_mut() {
+                    ForClauses::CStyle {
+                        initializer,
+                        condition,
+                        increment,
+                    } => {
+                        if let Some(initializer) = initializer {
+                            self.statement(initializer);
+                        }
+                        for expr in [condition, increment].into_iter().flatten() {
+                            self.expr(expr);
+                        }
+                    }
+                    ForClauses::In { pattern, iterable } => {
+                        self.expr(iterable);
+                        for name in pattern.bindings() {
+                            self.declare(name);
+                        }
+                    }
+                }
+                self.statement(body);
+                self.scopes.pop();
+            }
+            Stmt::Break(_, _, value, _) => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            Stmt::Continue(..) => {}
+            Stmt::Fn {
+                name, params, body, ..
+            } => {
+                // declared first, so the function can call itself
+                self.declare(name);
+                self.scopes.push(Vec::new());
+                self.params(params);
+                self.expr(body);
+                self.scopes.pop();
+            }
+            Stmt::Struct { .. } | Stmt::Enum { .. } => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &mut Expr<'src>) {
+        match expr {
+            Expr::Assign(name, value, _) | Expr::CompoundAssign(name, _, value, _) => {
+                self.reference(name);
+                self.expr(value);
+            }
+            Expr::Array(elements, _) | Expr::Tuple(elements, _) => {
+                for element in elements {
+                    self.expr(element);
+                }
+            }
+            Expr::ArrayRepeat(left, right, _)
+            | Expr::Binary(left, _, right, _)
+            | Expr::Index(left, right, _)
+            | Expr::Logical(left, _, right, _) => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Block(statements, tail, _) => {
+                self.scopes.push(Vec::new());
+                self.statements(statements);
+                if let Some(tail) = tail {
+                    self.expr(tail);
+                }
+                self.scopes.pop();
+            }
+            Expr::Call { callee, args, .. } => {
+                self.expr(callee);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::Cast(expr, ..)
+            | Expr::Get(expr, ..)
+            | Expr::Grouping(expr, _)
+            | Expr::Loop { body: expr, .. }
+            | Expr::Try(expr, _)
+            | Expr::Unary(_, expr, _) => self.expr(expr),
+            Expr::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.expr(condition);
+                self.expr(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.expr(else_branch);
+                }
+            }
+            Expr::Interpolation(parts, _) => {
+                for part in parts {
+                    if let Part::Expr(expr) = part {
+                        self.expr(expr);
+                    }
+                }
+            }
+            Expr::Lambda {
+                params,
+                body,
+                captures,
+                ..
+            } => {
+                self.closures.push((self.scopes.len(), Vec::new()));
+                self.scopes.push(Vec::new());
+                self.params(params);
+                self.expr(body);
+                self.scopes.pop();
+                if let Some((_, found)) = self.closures.pop() {
+                    *captures = found;
+                }
+            }
+            Expr::Match {
+                scrutinee, arms, ..
+            } => {
+                self.expr(scrutinee);
+                for arm in arms {
+                    self.scopes.push(Vec::new());
+                    for name in arm.pattern.bindings() {
+                        self.declare(name);
+                    }
+                    if let Some(guard) = &mut arm.guard {
+                        self.expr(guard);
+                    }
+                    self.expr(&mut arm.body);
+                    self.scopes.pop();
+                }
+            }
+            Expr::Range(start, _, end, _) => {
+                for expr in [start, end].into_iter().flatten() {
+                    self.expr(expr);
+                }
+            }
+            Expr::CompoundIndexSet(object, index, _, value, _)
+            | Expr::IndexSet(object, index, value, _) => {
+                self.expr(object);
+                self.expr(index);
+                self.expr(value);
+            }
+            Expr::CompoundSet(object, _, _, value, _) | Expr::Set(object, _, value, _) => {
+                self.expr(object);
+                self.expr(value);
+            }
+            Expr::StructLiteral(_, fields, _) => {
+                for (_, value) in fields {
+                    self.expr(value);
+                }
+            }
+            Expr::Variable(name, _) => self.reference(name),
+            Expr::Literal(..) | Expr::Path(..) => {}
+        }
+    }
+
+    fn params(&mut self, params: &mut [Param<'src>]) {
+        for param in params {
+            if let Some(default) = &mut param.default {
+                self.expr(default);
+            }
+            self.declare(&param.name);
+        }
+    }
+
+    fn declare(&mut self, name: &Token<'src>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(name.name());
+        }
+    }
+
+    /// Records a use of a variable in every closure between it and its declaration.
+    fn reference(&mut self, name: &Token<'src>) {
+        let key = name.name();
+        let Some(declared_in) = self.scopes.iter().rposition(|scope| scope.contains(&key)) else {
+            return;
+        };
+        if declared_in == 0 {
+            return;
+        }
+
+        for (outer_scopes, captures) in &mut self.closures {
+            if *outer_scopes > declared_in && !captures.iter().any(|c| c.name() == key) {
+                captures.push(name.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::analyze;
+    use crate::ast::{lexer::Lexer, parser::Parser, Expr, Stmt};
+
+    /// The captures of the closure assigned by each `let` in the body of `f`.
+    fn captures(source: &str) -> Vec<Vec<&str>> {
+        let mut ast = Parser::new(Lexer::new(source)).parse();
+        analyze(&mut ast);
+        let Stmt::Fn { body, .. } = &ast[1] else {
+            panic!()
+        };
+        let Expr::Block(statements, ..) = body.as_ref() else {
+            panic!()
+        };
+        statements
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::Let {
+                    initializer: Some(Expr::Lambda { captures, .. }),
+                    ..
+                } => Some(captures.iter().map(|token| token.lexeme).collect()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn closures_capture_outer_variables() {
+        let source = "
+            let global = 1;
+            fn f(a, b) {
+                let c = 3;
+                let add = fn(x) { let y = x; y + a + global };
+                let nested = |x| |y| x + y + b + c + b;
+                let shadowed = |a| a;
+                let assigns = || c += 1;
+            }
+        ";
+        assert_eq!(
+            captures(source),
+            [vec!["a"], vec!["b", "c"], vec![], vec!["c"]]
+        );
+    }
+}
@@ -0,0 +1,772 @@
+use super::{
+    Arm, Expr, Field, ForClauses, Literal, Param, Part, Pattern, Stmt, Token, Variant,
+    VariantFields,
+};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+/// A problem in a program that parses fine, but doesn't make sense.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum CheckError {
+    #[error("can't return from outside of a function")]
+    ReturnOutsideFunction,
+    #[error("can't break from outside of a loop")]
+    BreakOutsideLoop,
+    #[error("can't continue outside of a loop")]
+    ContinueOutsideLoop,
+    #[error("use of undeclared label `{0}`")]
+    UndeclaredLabel(String),
+    #[error("only `loop` can break with a value")]
+    BreakWithValue,
+    #[error("match isn't exhaustive, `{0}` isn't covered")]
+    NonExhaustiveMatch(String),
+    #[error("pattern might not match, `{0}` isn't covered")]
+    RefutablePattern(String),
+    #[error("`{0}` has {1} fields, but the pattern has {2}")]
+    PatternArity(String, usize, usize),
+    #[error("`{0}` has no field `{1}`")]
+    UnknownField(String, String),
+    #[error("`{0}` has named fields, which are matched with `{0} {{ ... }}`")]
+    PositionalFieldPatterns(String),
+}
+
+/// Runs the semantic checks that need more context than the parser has on a parsed
+/// program, returning each problem with the token it's reported at.
+#[must_use]
+pub fn check<'src>(statements: &[Stmt<'src>]) -> Vec<(Token<'src>, CheckError)> {
+    let mut checker = Checker::default();
+    // types can be used before they're declared
+    for stmt in statements {
+        checker.declare(stmt);
+    }
+    checker.statements(statements);
+    checker.errors
+}
+
+#[derive(Default)]
+struct Checker<'a, 'src> {
+    /// How many functions the statement being checked is nested in.
+    function_depth: usize,
+    /// The loops of the function around the statement being checked, innermost last,
+    /// with their labels and whether they're a `loop`, which can break with a value.
+    loops: Vec<(Option<Cow<'src, str>>, bool)>,
+    structs: HashMap<Cow<'src, str>, (&'a Token<'src>, &'a [Field<'src>])>,
+    enums: HashMap<Cow<'src, str>, (&'a Token<'src>, &'a [Variant<'src>])>,
+    errors: Vec<(Token<'src>, CheckError)>,
+}
+
+impl<'a, 'src> Checker<'a, 'src> {
+    fn declare(&mut self, stmt: &'a Stmt<'src>) {
+        match stmt {
+            Stmt::Struct { name, fields, .. } => {
+                self.structs.insert(name.name(), (name, fields));
+            }
+            Stmt::Enum { name, variants, .. } => {
+                self.enums.insert(name.name(), (name, variants));
+            }
+            _ => {}
+        }
+    }
+
+    fn statements(&mut self, statements: &'a [Stmt<'src>]) {
+        for stmt in statements {
+            self.statement(stmt);
+        }
+    }
+
+    fn statement(&mut self, stmt: &'a Stmt<'src>) {
+        match stmt {
+            Stmt::Expression(expr, _) => self.expr(expr),
+            Stmt::Let {
+                keyword,
+                pattern,
+                initializer,
+                ..
+            } => {
+                if let Some(initializer) = initializer {
+                    self.expr(initializer);
+                }
+                self.irrefutable(keyword, pattern);
+            }
+            Stmt::While {
+                label,
+                condition,
+                body,
+                increment,
+                ..
+            } => {
+                self.expr(condition);
+                self.loops.push((label.as_ref().map(Token::name), false));
+                self.statement(body);
+                if let Some(increment) = increment {
+                    self.expr(increment);
+                }
+                self.loops.pop();
+            }
+            Stmt::For {
+                label,
+                keyword,
+                clauses,
+                body,
+                ..
+            } => {
+                let increment = match clauses.as_ref() {
+                    ForClauses::CStyle {
+                        initializer,
+                        condition,
+                        increment,
+                    } => {
+                        if let Some(initializer) = initializer {
+                            self.statement(initializer);
+                        }
+                        if let Some(condition) = condition {
+                            self.expr(condition);
+                        }
+                        increment.as_ref()
+                    }
+                    ForClauses::In { pattern, iterable } => {
+                        self.expr(iterable);
+                        self.irrefutable(keyword, pattern);
+                        None
+                    }
+                };
+                self.loops.push((label.as_ref().map(Token::name), false));
+                self.statement(body);
+                if let Some(increment) = increment {
+                    self.expr(increment);
+                }
+                self.loops.pop();
+            }
+            Stmt::Break(keyword, label, value, _) => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+                let is_loop =
+                    self.loop_target(keyword, label.as_ref(), CheckError::BreakOutsideLoop);
+                if value.is_some() && is_loop == Some(false) {
+                    self.error(keyword, CheckError::BreakWithValue);
+                }
+            }
+            Stmt::Continue(keyword, label, _) => {
+                self.loop_target(keyword, label.as_ref(), CheckError::ContinueOutsideLoop);
+            }
+            Stmt::Fn { params, body, .. } => {
+                self.params(params);
+                let loops = std::mem::take(&mut self.loops);
+                self.function_depth += 1;
+                self.expr(body);
+                self.function_depth -= 1;
+                self.loops = loops;
+            }
+            Stmt::Return(keyword, value, _) => {
+                if self.function_depth == 0 {
+                    self.error(keyword, CheckError::ReturnOutsideFunction);
+                }
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            Stmt::Struct { .. } | Stmt::Enum { .. } => self.declare(stmt),
+        }
+    }
+
+    fn expr(&mut self, expr: &'a Expr<'src>) {
+        match expr {
+            Expr::Assign(_, value, _) | Expr::CompoundAssign(_, _, value, _) => self.expr(value),
+            Expr::Array(elements, _) | Expr::Tuple(elements, _) => {
+                for element in elements {
+                    self.expr(element);
+                }
+            }
+            Expr::ArrayRepeat(left, right, _)
+            | Expr::Binary(left, _, right, _)
+            | Expr::CompoundSet(left, _, _, right, _)
+            | Expr::Index(left, right, _)
+            | Expr::Logical(left, _, right, _)
+            | Expr::Set(left, _, right, _) => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Block(statements, tail, _) => {
+                self.statements(statements);
+                if let Some(tail) = tail {
+                    self.expr(tail);
+                }
+            }
+            Expr::Call { callee, args, .. } => {
+                self.expr(callee);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::Cast(expr, ..)
+            | Expr::Get(expr, ..)
+            | Expr::Grouping(expr, _)
+            | Expr::Try(expr, _)
+            | Expr::Unary(_, expr, _) => self.expr(expr),
+            Expr::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.expr(condition);
+                self.expr(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.expr(else_branch);
+                }
+            }
+            Expr::CompoundIndexSet(object, index, _, value, _)
+            | Expr::IndexSet(object, index, value, _) => {
+                self.expr(object);
+                self.expr(index);
+                self.expr(value);
+            }
+            Expr::Interpolation(parts, _) => {
+                for part in parts {
+                    if let Part::Expr(expr) = part {
+                        self.expr(expr);
+                    }
+                }
+            }
+            Expr::Lambda { params, body, .. } => {
+                self.params(params);
+                let loops = std::mem::take(&mut self.loops);
+                self.function_depth += 1;
+                self.expr(body);
+                self.function_depth -= 1;
+                self.loops = loops;
+            }
+            Expr::Loop { label, body, .. } => {
+                self.loops.push((label.as_ref().map(Token::name), true));
+                self.expr(body);
+                self.loops.pop();
+            }
+            Expr::Match {
+                keyword,
+                scrutinee,
+                arms,
+                ..
+            } => {
+                self.expr(scrutinee);
+                for arm in arms {
+                    if let Some(guard) = &arm.guard {
+                        self.expr(guard);
+                    }
+                    self.expr(&arm.body);
+                }
+                self.exhaustiveness(keyword, arms);
+            }
+            Expr::Range(start, _, end, _) => {
+                for expr in [start, end].into_iter().flatten() {
+                    self.expr(expr);
+                }
+            }
+            Expr::StructLiteral(_, fields, _) => {
+                for (_, value) in fields {
+                    self.expr(value);
+                }
+            }
+            Expr::Literal(..) | Expr::Path(..) | Expr::Variable(..) => {}
+        }
+    }
+
+    fn params(&mut self, params: &'a [Param<'src>]) {
+        for default in params.iter().filter_map(|param| param.default.as_ref()) {
+            self.expr(default);
+        }
+    }
+
+    /// Finds the loop a `break` or `continue` applies to, returning whether it's a `loop`,
+    /// or reports `outside` if there's no loop around it.
+    fn loop_target(
+        &mut self,
+        keyword: &Token<'src>,
+        label: Option<&Token<'src>>,
+        outside: CheckError,
+    ) -> Option<bool> {
+        if self.loops.is_empty() {
+            self.error(keyword, outside);
+            return None;
+        }
+        let Some(label) = label else {
+            return self.loops.last().map(|&(_, is_loop)| is_loop);
+        };
+        let name = label.name();
+        let target = self
+            .loops
+            .iter()
+            .rfind(|(other, _)| other.as_ref() == Some(&name))
+            .map(|&(_, is_loop)| is_loop);
+        if target.is_none() {
+            self.error(label, CheckError::UndeclaredLabel(label.lexeme.to_owned()));
+        }
+        target
+    }
+
+    /// Reports a value the pattern of a `let` or `for` doesn't match, since there's no
+    /// other arm to take.
+    fn irrefutable(&mut self, keyword: &Token<'src>, pattern: &'a Pattern<'src>) {
+        let rows = [vec![self.lower(pattern)]];
+        if let Some(witness) = useful(&rows, &[Pat::Wild]) {
+            let missing = witness.first().map_or_else(String::new, Pat::to_string);
+            self.error(keyword, CheckError::RefutablePattern(missing));
+        }
+    }
+
+    /// Reports a value the arms of a match don't cover. Arms with a guard might not
+    /// match, so they don't count.
+    fn exhaustiveness(&mut self, keyword: &Token<'src>, arms: &'a [Arm<'src>]) {
+        let rows: Vec<_> = arms
+            .iter()
+            .filter(|arm| arm.guard.is_none())
+            .map(|arm| vec![self.lower(&arm.pattern)])
+            .collect();
+        if let Some(witness) = useful(&rows, &[Pat::Wild]) {
+            let missing = witness.first().map_or_else(String::new, Pat::to_string);
+            self.error(keyword, CheckError::NonExhaustiveMatch(missing));
+        }
+    }
+
+    /// Reduces a pattern to the constructors it matches, looking up the declarations of
+    /// the structs and enums it names, and reports fields that don't fit them.
+    fn lower(&mut self, pattern: &'a Pattern<'src>) -> Pat<'a, 'src> {
+        match pattern {
+            Pattern::Binding(..) | Pattern::Wildcard(_) => Pat::Wild,
+            Pattern::Literal(Literal::Bool(value), _) => Pat::Ctor(Ctor::Bool(*value), Vec::new()),
+            Pattern::Literal(literal, _) => {
+                Pat::Ctor(Ctor::Opaque(format!("{literal:?}")), Vec::new())
+            }
+            Pattern::Negative(literal, _) => {
+                Pat::Ctor(Ctor::Opaque(format!("-{literal:?}")), Vec::new())
+            }
+            Pattern::Tuple(patterns, _) => Pat::Ctor(
+                Ctor::Tuple(patterns.len()),
+                patterns.iter().map(|pattern| self.lower(pattern)).collect(),
+            ),
+            Pattern::Struct(path, fields, _) => {
+                let declared = match &path[..] {
+                    [name] => self
+                        .structs
+                        .get(&name.name())
+                        .map(|&(name, declared)| (Ctor::Struct(name, declared), declared)),
+                    [..] => self
+                        .variant(path)
+                        .and_then(|(ctor, variant)| match &variant.fields {
+                            VariantFields::Struct(declared) => Some((ctor, &declared[..])),
+                            _ => None,
+                        }),
+                };
+                // the fields of tuple and unit variants have no names
+                let known = declared.as_ref().map_or(&[][..], |&(_, declared)| declared);
+                if declared.is_some() || self.variant(path).is_some() {
+                    for (name, _) in fields {
+                        if !known.iter().any(|field| field.name.name() == name.name()) {
+                            let error =
+                                CheckError::UnknownField(path_name(path), name.lexeme.to_owned());
+                            self.error(name, error);
+                        }
+                    }
+                }
+                match declared {
+                    Some((ctor, declared)) => {
+                        // fields are matched in the order they're declared, and left out
+                        // ones match anything
+                        let args = declared
+                            .iter()
+                            .map(|field| {
+                                fields
+                                    .iter()
+                                    .find(|(name, _)| name.name() == field.name.name())
+                                    .map_or(Pat::Wild, |(_, pattern)| self.lower(pattern))
+                            })
+                            .collect();
+                        Pat::Ctor(ctor, args)
+                    }
+                    None => Pat::Ctor(Ctor::Opaque(path_name(path)), Vec::new()),
+                }
+            }
+            Pattern::Variant(path, patterns, _) => match self.variant(path) {
+                Some((ctor, variant)) => {
+                    let name = &path[path.len() - 1];
+                    let expected = variant.fields.len();
+                    if matches!(variant.fields, VariantFields::Struct(_)) {
+                        self.error(name, CheckError::PositionalFieldPatterns(path_name(path)));
+                    } else if patterns.len() != expected {
+                        let error =
+                            CheckError::PatternArity(path_name(path), expected, patterns.len());
+                        self.error(name, error);
+                    }
+                    // padded or cut to the declared fields after an error, so the rows of
+                    // the exhaustiveness check still line up
+                    let mut args: Vec<_> =
+                        patterns.iter().map(|pattern| self.lower(pattern)).collect();
+                    args.resize(expected, Pat::Wild);
+                    Pat::Ctor(ctor, args)
+                }
+                None => Pat::Ctor(Ctor::Opaque(path_name(path)), Vec::new()),
+            },
+        }
+    }
+
+    /// Looks up the enum variant an `Enum::Variant` path names.
+    fn variant(&self, path: &[Token<'src>]) -> Option<(Ctor<'a, 'src>, &'a Variant<'src>)> {
+        let [enum_name, variant_name] = path else {
+            return None;
+        };
+        let (name, variants) = *self.enums.get(&enum_name.name())?;
+        let index = variants
+            .iter()
+            .position(|variant| variant.name.name() == variant_name.name())?;
+        Some((Ctor::Variant(name, variants, index), &variants[index]))
+    }
+
+    fn error(&mut self, token: &Token<'src>, error: CheckError) {
+        self.errors.push((token.clone(), error));
+    }
+}
+
+fn path_name(path: &[Token<'_>]) -> String {
+    path.iter()
+        .map(|segment| segment.lexeme)
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// A pattern reduced to what matters for exhaustiveness: the constructor of the values
+/// it matches, if it's limited to one, and the patterns of the constructor's fields.
+#[derive(Debug, Clone, PartialEq)]
+enum Pat<'a, 'src> {
+    Wild,
+    Ctor(Ctor<'a, 'src>, Vec<Pat<'a, 'src>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Ctor<'a, 'src> {
+    Bool(bool),
+    Tuple(usize),
+    Struct(&'a Token<'src>, &'a [Field<'src>]),
+    /// The variant at an index of an enum.
+    Variant(&'a Token<'src>, &'a [Variant<'src>], usize),
+    /// A value of a type with too many to list, like a number, or of an undeclared type.
+    Opaque(String),
+}
+
+impl<'a, 'src> Ctor<'a, 'src> {
+    /// How many fields the constructor has.
+    fn arity(&self) -> usize {
+        match self {
+            Self::Bool(_) | Self::Opaque(_) => 0,
+            Self::Tuple(arity) => *arity,
+            Self::Struct(_, fields) => fields.len(),
+            Self::Variant(_, variants, index) => variants[*index].fields.len(),
+        }
+    }
+
+    /// All the constructors of the constructor's type, if there are few enough to list.
+    fn siblings(&self) -> Option<Vec<Self>> {
+        match self {
+            Self::Bool(_) => Some(vec![Self::Bool(true), Self::Bool(false)]),
+            Self::Tuple(_) | Self::Struct(..) => Some(vec![self.clone()]),
+            Self::Variant(name, variants, _) => Some(
+                (0..variants.len())
+                    .map(|index| Self::Variant(name, variants, index))
+                    .collect(),
+            ),
+            Self::Opaque(_) => None,
+        }
+    }
+}
+
+/// Finds values that `row` matches but none of `rows` do, following "Warnings for
+/// pattern matching" by Luc Maranget. Returns one such value as a pattern for each
+/// column, or `None` if `rows` cover everything `row` does.
+fn useful<'a, 'src>(
+    rows: &[Vec<Pat<'a, 'src>>],
+    row: &[Pat<'a, 'src>],
+) -> Option<Vec<Pat<'a, 'src>>> {
+    let Some((head, tail)) = row.split_first() else {
+        return rows.is_empty().then(Vec::new);
+    };
+    match head {
+        Pat::Ctor(ctor, args) => {
+            let row = [&args[..], tail].concat();
+            useful(&specialize(rows, ctor), &row).map(|witness| rebuild(ctor, witness))
+        }
+        Pat::Wild => {
+            let used: Vec<_> = rows
+                .iter()
+                .filter_map(|row| match &row[0] {
+                    Pat::Ctor(ctor, _) => Some(ctor),
+                    Pat::Wild => None,
+                })
+                .collect();
+            let siblings = used.first().and_then(|ctor| ctor.siblings());
+            match siblings {
+                Some(all) if all.iter().all(|ctor| used.contains(&ctor)) => {
+                    all.into_iter().find_map(|ctor| {
+                        let row = [&vec![Pat::Wild; ctor.arity()][..], tail].concat();
+                        useful(&specialize(rows, &ctor), &row)
+                            .map(|witness| rebuild(&ctor, witness))
+                    })
+                }
+                siblings => {
+                    // some constructor isn't covered, so only the rows that match any
+                    // value in this column matter
+                    let rest: Vec<_> = rows
+                        .iter()
+                        .filter(|row| row[0] == Pat::Wild)
+                        .map(|row| row[1..].to_vec())
+                        .collect();
+                    let mut witness = useful(&rest, tail)?;
+                    let missing = siblings
+                        .and_then(|all| all.into_iter().find(|ctor| !used.contains(&ctor)))
+                        .map_or(Pat::Wild, |ctor| {
+                            let args = vec![Pat::Wild; ctor.arity()];
+                            Pat::Ctor(ctor, args)
+                        });
+                    witness.insert(0, missing);
+                    Some(witness)
+                }
+            }
+        }
+    }
+}
+
+/// The rows that can match a value built with `ctor`, with their first column replaced
+/// by the patterns of its fields.
+fn specialize<'a, 'src>(
+    rows: &[Vec<Pat<'a, 'src>>],
+    ctor: &Ctor<'a, 'src>,
+) -> Vec<Vec<Pat<'a, 'src>>> {
+    rows.iter()
+        .filter_map(|row| match &row[0] {
+            Pat::Ctor(other, args) if other == ctor => Some([&args[..], &row[1..]].concat()),
+            Pat::Ctor(..) => None,
+            Pat::Wild => Some([&vec![Pat::Wild; ctor.arity()][..], &row[1..]].concat()),
+        })
+        .collect()
+}
+
+/// Undoes [`specialize`] on a witness, putting the patterns of the fields of `ctor` back
+/// together.
+fn rebuild<'a, 'src>(ctor: &Ctor<'a, 'src>, mut witness: Vec<Pat<'a, 'src>>) -> Vec<Pat<'a, 'src>> {
+    let args = witness.drain(..ctor.arity()).collect();
+    witness.insert(0, Pat::Ctor(ctor.clone(), args));
+    witness
+}
+
+impl fmt::Display for Pat<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self::Ctor(ctor, args) = self else {
+            return write!(f, "_");
+        };
+        let list = |f: &mut fmt::Formatter<'_>, args: &[Self]| {
+            for (i, arg) in args.iter().enumerate() {
+                let separator = if i == 0 { "" } else { ", " };
+                write!(f, "{separator}{arg}")?;
+            }
+            Ok(())
+        };
+        let fields = |f: &mut fmt::Formatter<'_>, fields: &[Field<'_>]| {
+            if fields.is_empty() {
+                return write!(f, " {{}}");
+            }
+            write!(f, " {{ ")?;
+            for (i, (field, arg)) in fields.iter().zip(args).enumerate() {
+                let separator = if i == 0 { "" } else { ", " };
+                write!(f, "{separator}{}: {arg}", field.name.lexeme)?;
+            }
+            write!(f, " }}")
+        };
+        match ctor {
+            Ctor::Bool(value) => write!(f, "{value}"),
+            Ctor::Opaque(value) => write!(f, "{value}"),
+            Ctor::Tuple(1) => write!(f, "({},)", args[0]),
+            Ctor::Tuple(_) => {
+                write!(f, "(")?;
+                list(f, args)?;
+                write!(f, ")")
+            }
+            Ctor::Struct(name, declared) => {
+                write!(f, "{}", name.lexeme)?;
+                fields(f, declared)
+            }
+            Ctor::Variant(name, variants, index) => {
+                let variant = &variants[*index];
+                write!(f, "{}::{}", name.lexeme, variant.name.lexeme)?;
+                match &variant.fields {
+                    VariantFields::Unit => Ok(()),
+                    VariantFields::Tuple(_) => {
+                        write!(f, "(")?;
+                        list(f, args)?;
+                        write!(f, ")")
+                    }
+                    VariantFields::Struct(declared) => fields(f, declared),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{check, CheckError};
+    use crate::ast::{lexer::Lexer, parser::Parser};
+
+    #[test]
+    fn return_outside_function() {
+        let source = "fn f(a) {\n    if (a) { return a; }\n    return;\n}\nreturn 1;";
+        let ast = Parser::new(Lexer::new(source)).parse();
+        let errors: Vec<_> = check(&ast)
+            .into_iter()
+            .map(|(token, error)| (token.line, error))
+            .collect();
+        assert_eq!(errors, [(5, CheckError::ReturnOutsideFunction)]);
+    }
+
+    #[test]
+    fn loop_control() {
+        let source = "
+            'outer: while (true) {
+                for (;;) { continue 'outer; }
+                loop { break 'inner; }
+                let f = fn() { break; };
+                break 1;
+            }
+            let x = 'a: loop { while (x) { break 'a x; } };
+            continue;
+        ";
+        let ast = Parser::new(Lexer::new(source)).parse();
+        let errors: Vec<_> = check(&ast)
+            .into_iter()
+            .map(|(token, error)| (token.line, error))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (4, CheckError::UndeclaredLabel("'inner".to_owned())),
+                (5, CheckError::BreakOutsideLoop),
+                (6, CheckError::BreakWithValue),
+                (9, CheckError::ContinueOutsideLoop),
+            ]
+        );
+    }
+
+    /// The values reported as not covered by each match in `source`.
+    fn missing(source: &str) -> Vec<String> {
+        let declarations = "
+            enum Shape { Circle(f64), Rect { w: f64, h: f64 }, Empty }
+            struct Point { x: i32, y: i32 }
+        ";
+        let source = format!("{declarations}{source}");
+        let ast = Parser::new(Lexer::new(&source)).parse();
+        check(&ast)
+            .into_iter()
+            .map(|(_, error)| match error {
+                CheckError::NonExhaustiveMatch(missing) => missing,
+                error => panic!("{error}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn non_exhaustive_matches() {
+        let exhaustive = [
+            "match s { Shape::Circle(r) => r, Shape::Rect { w, h } => w * h, Shape::Empty => 0 }",
+            "match s { Shape::Circle(_) => 1, _ => 0 }",
+            "match b { true => 1, false => 0 }",
+            "match pair { (true, x) => x, (false, _) => 0 }",
+            "match p { Point { x: 0, y } => y, Point { x, y: _ } => x }",
+            "let f = |n| match n { 0 => 1, n if n > 0 => n, n => -n };",
+        ];
+        for source in exhaustive {
+            assert_eq!(missing(source), [] as [String; 0], "{source}");
+        }
+
+        assert_eq!(
+            missing("match s { Shape::Circle(r) if r > 1.0 => r, Shape::Empty => 0 }"),
+            ["Shape::Circle(_)"]
+        );
+        assert_eq!(
+            missing("match p { Point { x: 0, y } => y }"),
+            ["Point { x: _, y: _ }"]
+        );
+        assert_eq!(
+            missing("match pair { (true, false) => 1, (false, _) => 0 }"),
+            ["(true, true)"]
+        );
+        assert_eq!(missing("match n { 1 => 1, 2 => 2 }"), ["_"]);
+        assert_eq!(
+            missing("match s { Shape::Circle(r) => { match r { 0.0 => 0 } } }"),
+            ["_", "Shape::Rect { w: _, h: _ }"]
+        );
+    }
+    #[test]
+    fn refutable_patterns() {
+        let source = "
+            enum Shape { Circle(f64), Empty }
+            struct P { x: i32, y: i32 }
+            let (a, _) = pair;
+            let P { x, y: (b, c) } = p;
+            let 3 = x;
+            let Shape::Circle(r) = s;
+            for (i, -1) in pairs {}
+        ";
+        let ast = Parser::new(Lexer::new(source)).parse();
+        let errors: Vec<_> = check(&ast)
+            .into_iter()
+            .map(|(token, error)| (token.line, error))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (6, CheckError::RefutablePattern("_".to_owned())),
+                (7, CheckError::RefutablePattern("Shape::Empty".to_owned())),
+                (8, CheckError::RefutablePattern("(_, _)".to_owned())),
+            ]
+        );
+    }
+
+    #[test]
+    fn pattern_fields() {
+        let source = "
+            enum S { A(i32), B { x: i32 } }
+            struct P { x: i32 }
+            let v = match s {
+                S::A(a, b, c) => a,
+                S::A { q } => q,
+                S::B(x) => x,
+                S::B { x, y } => x,
+                P { z } => z,
+                _ => 0,
+            };
+        ";
+        let ast = Parser::new(Lexer::new(source)).parse();
+        let errors: Vec<_> = check(&ast)
+            .into_iter()
+            .map(|(token, error)| (token.lexeme, error))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                ("A", CheckError::PatternArity("S::A".to_owned(), 1, 3)),
+                (
+                    "q",
+                    CheckError::UnknownField("S::A".to_owned(), "q".to_owned())
+                ),
+                ("B", CheckError::PositionalFieldPatterns("S::B".to_owned())),
+                (
+                    "y",
+                    CheckError::UnknownField("S::B".to_owned(), "y".to_owned())
+                ),
+                (
+                    "z",
+                    CheckError::UnknownField("P".to_owned(), "z".to_owned())
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use super::{Span, Token, TokenType};
+use std::iter::Peekable;
+use std::vec::IntoIter;
+use TokenType::{
+    DocComment, Else, Eof, For, If, LeftBrace, LeftBracket, LeftParen, RightBrace, RightBracket,
+    RightParen, Semicolon, While,
+};
+
+/// A concrete syntax tree: every token of the source, trivia included, grouped into
+/// statements and delimited groups.
+///
+/// Unlike [`Stmt`](super::Stmt) and [`Expr`](super::Expr), nothing is dropped or
+/// desugared, so formatters and refactoring tools can edit the tree and print it back
+/// with [`SyntaxNode::to_source`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxNode<'src> {
+    pub kind: SyntaxKind,
+    pub children: Vec<SyntaxElement<'src>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SyntaxKind {
+    Root,
+    Statement,
+    /// `{ ... }`, containing statements.
+    Block,
+    /// `( ... )`
+    Parens,
+    /// `[ ... ]`
+    Brackets,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxElement<'src> {
+    Node(SyntaxNode<'src>),
+    Token(Token<'src>),
+}
+
+impl<'src> SyntaxNode<'src> {
+    /// Builds the tree from the tokens of a lossless [`Lexer`](super::lexer::Lexer).
+    #[must_use]
+    pub fn build(tokens: Vec<Token<'src>>) -> Self {
+        let mut builder = Builder {
+            tokens: tokens.into_iter().peekable(),
+        };
+        let mut children = builder.statements(false);
+        // the end of file token holds the trivia after the last statement
+        children.extend(builder.tokens.map(SyntaxElement::Token));
+        Self {
+            kind: SyntaxKind::Root,
+            children,
+        }
+    }
+
+    /// Iterates over all tokens in the tree, in source order.
+    pub fn tokens(&self) -> Box<dyn Iterator<Item = &Token<'src>> + '_> {
+        Box::new(self.children.iter().flat_map(|child| match child {
+            SyntaxElement::Node(node) => node.tokens(),
+            SyntaxElement::Token(token) => Box::new(std::iter::once(token)),
+        }))
+    }
+
+    #[must_use]
+    pub fn first_token(&self) -> Option<&Token<'src>> {
+        self.tokens().next()
+    }
+
+    /// The span of the node's tokens, excluding trivia.
+    #[must_use]
+    pub fn span(&self) -> Span {
+        let mut tokens = self.tokens();
+        let first = tokens.next().map_or_else(Span::default, |token| token.span);
+        tokens.fold(first, |span, token| span.to(token.span))
+    }
+
+    /// Reproduces the source text the tree was built from.
+    #[must_use]
+    pub fn to_source(&self) -> String {
+        let mut out = String::new();
+        for token in self.tokens() {
+            token.write_source(&mut out);
+        }
+        out
+    }
+}
+
+struct Builder<'src> {
+    tokens: Peekable<IntoIter<Token<'src>>>,
+}
+
+impl<'src> Builder<'src> {
+    fn statements(&mut self, in_block: bool) -> Vec<SyntaxElement<'src>> {
+        let mut statements = Vec::new();
+        loop {
+            match self.peek_type() {
+                None | Some(Eof) => break,
+                Some(RightBrace) if in_block => break,
+                _ => statements.push(SyntaxElement::Node(self.statement(in_block))),
+            }
+        }
+        statements
+    }
+
+    fn statement(&mut self, in_block: bool) -> SyntaxNode<'src> {
+        let mut children = Vec::new();
+        // doc comments belong to the statement they document
+        while self.peek_type() == Some(&DocComment) {
+            children.push(self.token());
+        }
+
+        let ends_with_block = matches!(
+            self.peek_type(),
+            Some(
+                If | While
+                    | For
+                    | TokenType::Fn
+                    | TokenType::Struct
+                    | TokenType::Enum
+                    | TokenType::Match
+                    | TokenType::Loop
+                    | TokenType::Label
+                    | LeftBrace
+            )
+        );
+        loop {
+            match self.peek_type() {
+                None | Some(Eof) => break,
+                Some(RightBrace) if in_block => break,
+                Some(LeftBrace) => {
+                    children.push(self.group(SyntaxKind::Block));
+                    if ends_with_block && self.peek_type() != Some(&Else) {
+                        break;
+                    }
+                }
+                Some(LeftParen) => children.push(self.group(SyntaxKind::Parens)),
+                Some(LeftBracket) => children.push(self.group(SyntaxKind::Brackets)),
+                Some(Semicolon) => {
+                    children.push(self.token());
+                    break;
+                }
+                Some(_) => children.push(self.token()),
+            }
+        }
+        SyntaxNode {
+            kind: SyntaxKind::Statement,
+            children,
+        }
+    }
+
+    fn group(&mut self, kind: SyntaxKind) -> SyntaxElement<'src> {
+        let mut children = vec![self.token()];
+        if kind == SyntaxKind::Block {
+            children.extend(self.statements(true));
+        } else {
+            let close = if kind == SyntaxKind::Parens {
+                RightParen
+            } else {
+                RightBracket
+            };
+            loop {
+                match self.peek_type() {
+                    None | Some(Eof) => break,
+                    Some(r#type) if *r#type == close => break,
+                    Some(LeftBrace) => children.push(self.group(SyntaxKind::Block)),
+                    Some(LeftParen) => children.push(self.group(SyntaxKind::Parens)),
+                    Some(LeftBracket) => children.push(self.group(SyntaxKind::Brackets)),
+                    Some(_) => children.push(self.token()),
+                }
+            }
+        }
+        // unclosed groups simply end at the end of the file
+        if !matches!(self.peek_type(), None | Some(Eof)) {
+            children.push(self.token());
+        }
+        SyntaxElement::Node(SyntaxNode { kind, children })
+    }
+
+    fn peek_type(&mut self) -> Option<&TokenType> {
+        self.tokens.peek().map(|token| &token.r#type)
+    }
+
+    fn token(&mut self) -> SyntaxElement<'src> {
+        SyntaxElement::Token(
+            self.tokens
+                .next()
+                .expect("peeked a token before consuming it"),
+        )
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{SyntaxElement, SyntaxKind, SyntaxNode};
+    use crate::ast::lexer::Lexer;
+
+    #[test]
+    fn round_trips_source() {
+        let source = "/// docs\nfn main() { // start\n    let a = [1, 2] ; /* a /* b */ */\n\tif (a) { b; } else { c; }\n}\r\n\n// trailing\n @ \"unterminated";
+        let tokens = Lexer::new(source).lossless().scan_tokens();
+        let mut text = String::new();
+        for token in &tokens {
+            token.write_source(&mut text);
+        }
+        assert_eq!(text, source);
+
+        let tree = SyntaxNode::build(tokens);
+        assert_eq!(tree.to_source(), source);
+
+        let SyntaxElement::Node(main) = &tree.children[0] else {
+            panic!()
+        };
+        assert_eq!(main.first_token().unwrap().lexeme, "/// docs");
+        let SyntaxElement::Node(body) = &main.children[4] else {
+            panic!()
+        };
+        assert_eq!(body.kind, SyntaxKind::Block);
+        // `{`, two statements and `}`
+        assert_eq!(body.children.len(), 4);
+    }
+}
@@ -0,0 +1,294 @@
+use super::lexer::{is_identifier_continue, Lexer};
+use super::{Span, Token, TokenError, TokenType, Trivia};
+
+/// How many characters past the end of a token the lexer may look at to decide where
+/// the token ends, like the `=` of `..=` or the `""` after `"` that makes a multiline
+/// string. The lookahead of raw strings and labels is unbounded, and handled apart.
+const LOOKAHEAD: usize = 3;
+
+/// Replaces the text in `range` of a source with `replacement`, like an editor would.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextEdit<'a> {
+    /// The replaced byte range of the old source.
+    pub range: Span,
+    pub replacement: &'a str,
+}
+
+impl<'a> TextEdit<'a> {
+    #[inline]
+    #[must_use]
+    pub const fn new(range: Span, replacement: &'a str) -> Self {
+        Self { range, replacement }
+    }
+
+    /// Returns the edited source.
+    ///
+    /// # Panics
+    ///
+    /// If the range is out of bounds or doesn't lie on character boundaries.
+    #[must_use]
+    pub fn apply(&self, source: &str) -> String {
+        let mut edited =
+            String::with_capacity(source.len() - self.range.len() + self.replacement.len());
+        edited.push_str(&source[..self.range.start]);
+        edited.push_str(self.replacement);
+        edited.push_str(&source[self.range.end..]);
+        edited
+    }
+
+    /// The range of the replacement in the edited source.
+    #[inline]
+    #[must_use]
+    pub const fn new_range(&self) -> Span {
+        Span::new(self.range.start, self.range.start + self.replacement.len())
+    }
+
+    /// Moves an offset of the old source after the edit to the edited source.
+    const fn shift(&self, offset: usize) -> usize {
+        offset - self.range.len() + self.replacement.len()
+    }
+
+    /// Moves an offset of the edited source after the edit back to the old source.
+    const fn unshift(&self, offset: usize) -> usize {
+        offset - self.replacement.len() + self.range.len()
+    }
+}
+
+impl<'src> Lexer<'src> {
+    /// Updates `old`, the tokens of a source before `edit`, to the source of this lexer,
+    /// which must be that source with `edit` applied.
+    ///
+    /// Only the tokens around the edit are scanned again. As soon as the lexer reaches a
+    /// token after the edit that matches an old one, with the same type and length at the
+    /// same place and outside of any string interpolation both before and after it, the
+    /// remaining old tokens can't change and are reused with their positions moved. The lexer must be configured like the one that produced `old`.
+    #[must_use]
+    pub fn relex(mut self, old: &[Token<'_>], edit: &TextEdit<'_>) -> Vec<Token<'src>> {
+        let source = self.source();
+        let outside = outside_interpolations(old);
+
+        // whether `r##"` starts a raw string or `'a` a label depends on the text after any
+        // number of `#`s or identifier characters, so the edit counts as starting before
+        // all of them
+        let edit_start = source[..edit.range.start]
+            .trim_end_matches(|ch| ch == '#' || is_identifier_continue(ch))
+            .len();
+
+        // the first token whose text or lookahead reaches into the edit, moved back to
+        // where the lexer has no interpolations to remember
+        let mut restart = old
+            .iter()
+            .position(|token| extent_end(token) + LOOKAHEAD > edit_start)
+            .unwrap_or(old.len());
+        // the trailing trivia of the token before runs right up to that one, and takes in
+        // whatever of its text turns into trivia
+        if restart > 0
+            && restart < old.len()
+            && old[restart].span.start == extent_end(&old[restart - 1])
+        {
+            restart -= 1;
+        }
+        while restart > 0 && !outside[restart - 1] {
+            restart -= 1;
+        }
+
+        let mut tokens: Vec<_> = old[..restart]
+            .iter()
+            .map(|token| {
+                rebase(
+                    token,
+                    source,
+                    |offset| offset,
+                    |line, column| (line, column),
+                )
+            })
+            .collect();
+        if let Some(previous) = old[..restart].last() {
+            self.resume(previous, extent_end(previous));
+        }
+
+        let edit_end = edit.new_range().end;
+        let mut candidate = restart;
+        loop {
+            let was_outside = !self.in_interpolation();
+            let Some(token) = self.next() else {
+                break;
+            };
+            if token.span.start < edit_end || self.in_interpolation() {
+                tokens.push(token);
+                continue;
+            }
+
+            let old_start = edit.unshift(token.span.start);
+            while old
+                .get(candidate)
+                .is_some_and(|old| old.span.start < old_start)
+            {
+                candidate += 1;
+            }
+            let Some(resync) = old.get(candidate).filter(|old| {
+                old.span.start == old_start
+                    && old.r#type == token.r#type
+                    && old.span.len() == token.span.len()
+                    && was_outside
+                    && (candidate == 0 || outside[candidate - 1])
+                    && outside[candidate]
+            }) else {
+                tokens.push(token);
+                continue;
+            };
+
+            // everything after the resync point is unchanged text, so only the lines
+            // and the columns on the resync token's line move
+            let (old_line, old_column) = (resync.line, resync.column);
+            let (new_line, new_column) = (token.line, token.column);
+            let position = |line: usize, column: usize| {
+                let column = if line == old_line {
+                    column - old_column + new_column
+                } else {
+                    column
+                };
+                (line - old_line + new_line, column)
+            };
+            tokens.push(token);
+            tokens.extend(
+                old[candidate + 1..]
+                    .iter()
+                    .map(|old| rebase(old, source, |offset| edit.shift(offset), position)),
+            );
+            break;
+        }
+        tokens
+    }
+}
+
+/// Whether the lexer is outside of all string interpolations after each token.
+fn outside_interpolations(tokens: &[Token<'_>]) -> Vec<bool> {
+    let mut depths: Vec<usize> = Vec::new();
+    tokens
+        .iter()
+        .map(|token| {
+            match token.r#type {
+                TokenType::InterpolationStart => depths.push(0),
+                TokenType::InterpolationEnd => {
+                    depths.pop();
+                }
+                TokenType::LeftBrace => {
+                    if let Some(depth) = depths.last_mut() {
+                        *depth += 1;
+                    }
+                }
+                TokenType::RightBrace => {
+                    if let Some(depth) = depths.last_mut() {
+                        *depth = depth.saturating_sub(1);
+                    }
+                }
+                _ => {}
+            }
+            depths.is_empty()
+        })
+        .collect()
+}
+
+/// The end of a token including its trailing trivia.
+fn extent_end(token: &Token<'_>) -> usize {
+    token
+        .trailing_trivia
+        .last()
+        .map_or(token.span.end, |trivia| trivia.span.end)
+}
+
+/// Copies a token of the old source into the edited one, moving its offsets with `shift`
+/// and its lines and columns with `position`.
+fn rebase<'src>(
+    token: &Token<'_>,
+    source: &'src str,
+    shift: impl Fn(usize) -> usize,
+    position: impl Fn(usize, usize) -> (usize, usize),
+) -> Token<'src> {
+    let span = Span::new(shift(token.span.start), shift(token.span.end));
+    let trivia = |trivia: &Vec<Trivia<'_>>| {
+        trivia
+            .iter()
+            .map(|trivia| {
+                let span = Span::new(shift(trivia.span.start), shift(trivia.span.end));
+                Trivia {
+                    kind: trivia.kind,
+                    text: &source[span.start..span.end],
+                    span,
+                }
+            })
+            .collect()
+    };
+    let (line, column) = position(token.line, token.column);
+    let mut rebased = Token::new(
+        token.r#type.clone(),
+        &source[span.start..span.end],
+        token.literal.clone(),
+        span,
+        line,
+        column,
+    );
+    rebased.error = token.error.as_ref().map(|error| {
+        let (line, column) = position(error.line, error.column);
+        Box::new(TokenError {
+            error: error.error.clone(),
+            span: Span::new(shift(error.span.start), shift(error.span.end)),
+            line,
+            column,
+        })
+    });
+    rebased.leading_trivia = trivia(&token.leading_trivia);
+    rebased.trailing_trivia = trivia(&token.trailing_trivia);
+    rebased
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::TextEdit;
+    use crate::ast::{lexer::Lexer, Span};
+
+    #[test]
+    fn relexes_edits() {
+        let source = "let a = 1; // one\nlet s = \"x {a + {b}} y\";\n/* c */ fn f() { a..=b }\nlet e = \"\\q\" @;\n";
+        let mut cases = vec![
+            (source, Span::new(4, 5), "abc"),
+            (source, Span::new(8, 9), "1.5"),
+            (source, Span::new(17, 17), "\nlet b = 2;"),
+            (source, Span::new(29, 30), "{"),
+            (source, Span::new(34, 35), ""),
+            (source, Span::new(42, 42), "\"\""),
+            (source, Span::new(44, 45), "*"),
+            (source, Span::new(62, 63), ""),
+            (source, Span::new(0, source.len()), ""),
+        ];
+        // a `}` where an interpolation used to end isn't the same token
+        cases.push(("x 'c' \"a { :: y } b\"", Span::new(12, 13), "} b\""));
+        // the `r` only starts a raw string once a quote follows all of its `#`s
+        cases.push(("let s = r####x;", Span::new(13, 14), "\""));
+        cases.push(("f('abcdefg);", Span::new(10, 10), "'"));
+        // the comment closed by the edit is trailing trivia of `a`
+        cases.push(("a /* y + /* E ! */ b", Span::new(10, 11), "{"));
+
+        for (source, range, replacement) in cases {
+            let edit = TextEdit::new(range, replacement);
+            let edited = edit.apply(source);
+            for lossless in [false, true] {
+                let lexer = |source| {
+                    let lexer = Lexer::new(source);
+                    if lossless {
+                        lexer.lossless()
+                    } else {
+                        lexer
+                    }
+                };
+                let old = lexer(source).scan_tokens();
+                assert_eq!(
+                    lexer(&edited).relex(&old, &edit),
+                    lexer(&edited).scan_tokens(),
+                    "{edited:?}"
+                );
+            }
+        }
+    }
+}
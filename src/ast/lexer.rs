@@ -1,233 +1,1325 @@
-use super::{Literal, Token, TokenType};
+use super::{
+    LexError, LexWarning, Literal, NumberSuffix, Span, Token, TokenError, TokenType, Trivia,
+    TriviaKind,
+};
 use core::clone;
 use core::str::FromStr as _;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::sync::LazyLock;
+use unicode_security::{skeleton, MixedScript as _};
+use unicode_xid::UnicodeXID as _;
 use TokenType::{
-    And, Bang, BangEqual, Comma, Dot, Else, Eof, Equal, EqualEqual, False, Fn, For, Greater,
-    GreaterEqual, Identifier, If, LeftBrace, LeftParen, Less, LessEqual, Let, Minus, Number, Or,
-    Plus, Return, RightBrace, RightParen, Semicolon, Slash, Star, True, While,
+    Amp, And, Arrow, As, Bang, BangEqual, Break, Caret, Colon, ColonColon, Comma, Continue,
+    DocComment, Dot, DotDot, DotDotEqual, Else, Enum, Eof, Equal, EqualEqual, Error, False,
+    FatArrow, Fn, For, Greater, GreaterEqual, GreaterGreater, Identifier, If, In, InterpolationEnd,
+    InterpolationMiddle, InterpolationStart, Label, LeftBrace, LeftBracket, LeftParen, Less,
+    LessEqual, LessLess, Let, Loop, Match, Minus, MinusEqual, Number, Or, Percent, PercentEqual,
+    Pipe, Plus, PlusEqual, Question, Return, RightBrace, RightBracket, RightParen, Semicolon,
+    Slash, SlashEqual, Star, StarEqual, Struct, Tilde, True, While,
 };
 
 static KEYWORDS: LazyLock<HashMap<String, TokenType>> = LazyLock::new(|| {
     let mut map = HashMap::new();
     map.insert("and".to_owned(), And);
+    map.insert("as".to_owned(), As);
+    map.insert("break".to_owned(), Break);
+    map.insert("continue".to_owned(), Continue);
     map.insert("else".to_owned(), Else);
+    map.insert("enum".to_owned(), Enum);
     map.insert("false".to_owned(), False);
     map.insert("for".to_owned(), For);
     map.insert("fn".to_owned(), Fn);
     map.insert("if".to_owned(), If);
+    map.insert("in".to_owned(), In);
+    map.insert("match".to_owned(), Match);
     map.insert("or".to_owned(), Or);
     map.insert("return".to_owned(), Return);
+    map.insert("struct".to_owned(), Struct);
     map.insert("true".to_owned(), True);
     map.insert("let".to_owned(), Let);
+    map.insert("loop".to_owned(), Loop);
     map.insert("while".to_owned(), While);
     map
 });
 
-pub struct Lexer {
-    source: Vec<char>,
-    tokens: Vec<Token>,
+/// Splits source text into [`Token`]s on demand.
+///
+/// Tokens borrow their lexemes from the source, and the lexer only ever looks at the
+/// characters after its current position, so scanning a file allocates nothing but
+/// decoded string literals (and trivia, in [lossless](Lexer::lossless) mode).
+#[derive(Clone)]
+pub struct Lexer<'src> {
+    source: &'src str,
+    /// Byte offset of the start of the current token.
     start: usize,
+    /// Byte offset of the next character.
     current: usize,
     line: usize,
+    column: usize,
+    start_line: usize,
+    start_column: usize,
+    lossless: bool,
+    pending_trivia: Vec<Trivia<'src>>,
+    finished: bool,
+    /// The first problem found in the current token.
+    error: Option<TokenError>,
+    /// The brace depth inside each string interpolation the lexer is in.
+    interpolations: Vec<usize>,
+    confusables: Confusables<'src>,
+    warnings: Vec<LexWarning>,
 }
 
-impl Lexer {
+impl<'src> Lexer<'src> {
     #[must_use]
-    pub fn new(source: &str) -> Self {
-        let src = source.chars();
-        let mut source = vec![];
-        for ch in src {
-            source.push(ch);
-        }
+    pub fn new(source: &'src str) -> Self {
         Self {
             source,
-            tokens: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
+            column: 1,
+            start_line: 1,
+            start_column: 1,
+            lossless: false,
+            pending_trivia: Vec::new(),
+            finished: false,
+            error: None,
+            interpolations: Vec::new(),
+            confusables: Confusables::default(),
+            warnings: Vec::new(),
         }
     }
 
-    pub fn scan_tokens(&mut self) -> Vec<Token> {
-        while !self.is_at_end() {
-            self.start = self.current;
-            self.scan_token();
+    /// Records whitespace and comments as [`Trivia`] on the tokens, so that the source
+    /// can be reproduced byte-for-byte from them.
+    #[must_use]
+    pub fn lossless(mut self) -> Self {
+        self.lossless = true;
+        self
+    }
+
+    /// Scans the whole source, including the final [`Eof`] token.
+    #[must_use]
+    pub fn scan_tokens(self) -> Vec<Token<'src>> {
+        self.collect()
+    }
+
+    /// The warnings about the tokens scanned so far, for the caller to report.
+    #[must_use]
+    pub fn warnings(&self) -> &[LexWarning] {
+        &self.warnings
+    }
+
+    pub(super) const fn source(&self) -> &'src str {
+        self.source
+    }
+
+    pub(super) fn in_interpolation(&self) -> bool {
+        !self.interpolations.is_empty()
+    }
+
+    /// Continues scanning at `offset`, somewhere after the start of `token`.
+    pub(super) fn resume(&mut self, token: &Token<'_>, offset: usize) {
+        self.current = token.span.start;
+        self.line = token.line;
+        self.column = token.column;
+        while self.current < offset {
+            self.advance();
         }
-        self.tokens.push(Token::new(Eof, "", None, self.line));
-        self.tokens.clone()
     }
 
     fn is_at_end(&self) -> bool {
         self.current >= self.source.len()
     }
 
-    fn scan_token(&mut self) {
+    /// Skips trivia before the next token, keeping it around in lossless mode.
+    fn skip_trivia(&mut self) {
+        while !self.is_at_end() {
+            let Some(trivia) = self.scan_trivia(true) else {
+                return;
+            };
+            if self.lossless {
+                self.pending_trivia.push(trivia);
+            }
+        }
+    }
+
+    /// Collects the trivia after a token, up to the end of its line.
+    fn trailing_trivia(&mut self, token: &mut Token<'src>) {
+        while let Some(trivia) = self.scan_trivia(false) {
+            let ends_line = trivia.text.contains('\n');
+            token.trailing_trivia.push(trivia);
+            if ends_line {
+                return;
+            }
+        }
+    }
+
+    fn scan_trivia(&mut self, newlines: bool) -> Option<Trivia<'src>> {
+        self.start_token();
+        let kind = match (self.peek(), self.peek_next()) {
+            (' ' | '\r' | '\t', _) => {
+                while matches!(self.peek(), ' ' | '\r' | '\t') {
+                    self.advance();
+                }
+                TriviaKind::Whitespace
+            }
+            ('\n', _) if newlines => {
+                self.advance();
+                TriviaKind::Newline
+            }
+            ('/', '/') if !self.at_doc_comment() => {
+                while self.peek() != '\n' && !self.is_at_end() {
+                    self.advance();
+                }
+                TriviaKind::LineComment
+            }
+            ('/', '*') => {
+                let checkpoint = (self.current, self.line, self.column);
+                self.advance();
+                self.advance();
+                if !self.block_comment() {
+                    // left for `scan_token` to turn into an error token
+                    (self.current, self.line, self.column) = checkpoint;
+                    return None;
+                }
+                TriviaKind::BlockComment
+            }
+            _ => return None,
+        };
+        Some(Trivia {
+            kind,
+            text: self.lexeme(),
+            span: self.span(),
+        })
+    }
+
+    fn scan_token(&mut self) -> Token<'src> {
         let ch = self.advance();
         match ch {
-            '(' => self.add_token(LeftParen),
-            ')' => self.add_token(RightParen),
-            '{' => self.add_token(LeftBrace),
-            '}' => self.add_token(RightBrace),
-            ',' => self.add_token(Comma),
-            '.' => self.add_token(Dot),
-            '-' => self.add_token(Minus),
-            '+' => self.add_token(Plus),
-            ';' => self.add_token(Semicolon),
-            '*' => self.add_token(Star),
+            '(' => self.token(LeftParen),
+            ')' => self.token(RightParen),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.token(LeftBrace)
+            }
+            '}' => match self.interpolations.last_mut() {
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.string(true)
+                }
+                Some(depth) => {
+                    *depth -= 1;
+                    self.token(RightBrace)
+                }
+                None => self.token(RightBrace),
+            },
+            '[' => self.token(LeftBracket),
+            ']' => self.token(RightBracket),
+            ',' => self.token(Comma),
+            ';' => self.token(Semicolon),
+            '^' => self.token(Caret),
+            '~' => self.token(Tilde),
+            '?' => self.token(Question),
+            '.' => {
+                let type_ = if self.matches('.') {
+                    if self.matches('=') {
+                        DotDotEqual
+                    } else {
+                        DotDot
+                    }
+                } else {
+                    Dot
+                };
+                self.token(type_)
+            }
+            ':' => {
+                let type_ = if self.matches(':') { ColonColon } else { Colon };
+                self.token(type_)
+            }
+            '-' => {
+                let type_ = if self.matches('>') {
+                    Arrow
+                } else if self.matches('=') {
+                    MinusEqual
+                } else {
+                    Minus
+                };
+                self.token(type_)
+            }
+            '+' => {
+                let type_ = if self.matches('=') { PlusEqual } else { Plus };
+                self.token(type_)
+            }
+            '*' => {
+                let type_ = if self.matches('=') { StarEqual } else { Star };
+                self.token(type_)
+            }
+            '%' => {
+                let type_ = if self.matches('=') {
+                    PercentEqual
+                } else {
+                    Percent
+                };
+                self.token(type_)
+            }
+            // `&&` and `||` are aliases of `and` and `or`
+            '&' => {
+                let type_ = if self.matches('&') { And } else { Amp };
+                self.token(type_)
+            }
+            '|' => {
+                let type_ = if self.matches('|') { Or } else { Pipe };
+                self.token(type_)
+            }
             '!' => {
                 let type_ = if self.matches('=') { BangEqual } else { Bang };
-                self.add_token(type_);
+                self.token(type_)
             }
             '=' => {
-                let type_ = if self.matches('=') { EqualEqual } else { Equal };
-                self.add_token(type_);
+                let type_ = if self.matches('=') {
+                    EqualEqual
+                } else if self.matches('>') {
+                    FatArrow
+                } else {
+                    Equal
+                };
+                self.token(type_)
             }
             '<' => {
-                let type_ = if self.matches('=') { LessEqual } else { Less };
-                self.add_token(type_);
+                let type_ = if self.matches('=') {
+                    LessEqual
+                } else if self.matches('<') {
+                    LessLess
+                } else {
+                    Less
+                };
+                self.token(type_)
             }
             '>' => {
                 let type_ = if self.matches('=') {
                     GreaterEqual
+                } else if self.matches('>') {
+                    GreaterGreater
                 } else {
                     Greater
                 };
-                self.add_token(type_);
+                self.token(type_)
             }
             '/' => {
                 if self.matches('/') {
-                    while self.peek() != '\n' && !self.is_at_end() {
-                        self.advance();
-                    }
+                    self.doc_comment()
+                } else if self.matches('=') {
+                    self.token(SlashEqual)
+                } else if self.matches('*') {
+                    // terminated block comments are trivia
+                    self.block_comment();
+                    self.error(LexError::UnterminatedBlockComment);
+                    self.token(Error)
                 } else {
-                    self.add_token(Slash);
+                    self.token(Slash)
                 }
             }
-            ' ' | '\r' | '\t' => {}
-            '\n' => self.increment_line(),
-            '"' => self.string(),
-            '\0' => self.add_token(Eof),
+            '"' => {
+                if self.peek() == '"' && self.peek_next() == '"' {
+                    self.multiline_string()
+                } else {
+                    self.string(false)
+                }
+            }
+            '\'' => self.char(),
+            'r' if self.at_raw_string() => self.raw_string(),
             _ => {
                 if ch.is_ascii_digit() {
-                    self.number();
-                } else if is_alphanumeric(ch) {
-                    self.identifier();
+                    self.number()
+                } else if is_identifier_start(ch) {
+                    self.identifier()
                 } else {
-                    crate::error(self.line, "unexpected character");
+                    self.error(LexError::UnexpectedCharacter(ch));
+                    self.token(Error)
                 }
             }
         }
     }
 
     fn advance(&mut self) -> char {
-        self.current += 1;
-        self.source.get(self.current - 1).copied().unwrap_or('\0')
+        let Some(ch) = self.source[self.current..].chars().next() else {
+            return '\0';
+        };
+        self.current += ch.len_utf8();
+        if ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        ch
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            '\0'
-        } else {
-            self.source.get(self.current).copied().unwrap()
-        }
+        self.peek_nth(0)
     }
 
     fn peek_next(&self) -> char {
-        if (self.current + 1) >= self.source.len() {
-            '\0'
+        self.peek_nth(1)
+    }
+
+    fn peek_nth(&self, n: usize) -> char {
+        self.source[self.current..].chars().nth(n).unwrap_or('\0')
+    }
+
+    fn token(&self, type_: TokenType) -> Token<'src> {
+        self.literal_token(type_, None)
+    }
+
+    fn literal_token(&self, type_: TokenType, literal: Option<Literal>) -> Token<'src> {
+        Token::new(
+            type_,
+            self.lexeme(),
+            literal,
+            self.span(),
+            self.start_line,
+            self.start_column,
+        )
+    }
+
+    fn lexeme(&self) -> &'src str {
+        &self.source[self.start..self.current]
+    }
+
+    const fn span(&self) -> Span {
+        Span::new(self.start, self.current)
+    }
+
+    fn start_token(&mut self) {
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column;
+    }
+
+    /// Records a problem with the current token, reported at its start. Only the first
+    /// error of a token is kept.
+    fn error(&mut self, error: LexError) {
+        self.error_at(error, (self.start, self.start_line, self.start_column));
+    }
+
+    /// Records a problem with the current token at the given offset, line and column,
+    /// from there up to the current position.
+    fn error_at(&mut self, error: LexError, (offset, line, column): (usize, usize, usize)) {
+        self.error.get_or_insert(TokenError {
+            error,
+            span: Span::new(offset, self.current),
+            line,
+            column,
+        });
+    }
+
+    fn matches(&mut self, expected: char) -> bool {
+        if self.peek() == expected && !self.is_at_end() {
+            self.advance();
+            true
         } else {
-            self.source.get(self.current + 1).copied().unwrap()
+            false
         }
     }
 
-    fn add_token(&mut self, type_: TokenType) {
-        self.add_full_token(type_, None);
+    /// Whether a `///` or `//!` doc comment starts at the current position.
+    fn at_doc_comment(&self) -> bool {
+        let rest = &self.source[self.current..];
+        // `////` and longer are regular comments
+        rest.starts_with("//!") || (rest.starts_with("///") && !rest.starts_with("////"))
     }
 
-    fn add_full_token(&mut self, type_: TokenType, literal: Option<Literal>) {
-        let mut lexeme_dyn = String::new();
-        for i in self.start..self.current {
-            lexeme_dyn.push(self.source[i]);
+    /// Scans a `///` or `//!` comment, after its first two slashes.
+    fn doc_comment(&mut self) -> Token<'src> {
+        while self.peek() != '\n' && !self.is_at_end() {
+            self.advance();
         }
+        let text = self.source[self.start + 3..self.current].to_owned();
+        self.literal_token(DocComment, Some(Literal::String(text)))
+    }
 
-        let token = Token::new(type_, &lexeme_dyn, literal, self.line);
-        self.tokens.push(token);
+    /// Skips a `/* ... */` comment, which may contain nested block comments.
+    ///
+    /// Returns whether the comment was terminated.
+    fn block_comment(&mut self) -> bool {
+        let mut depth = 1;
+        while depth > 0 {
+            if self.is_at_end() {
+                return false;
+            }
+            match self.advance() {
+                '/' if self.peek() == '*' => {
+                    self.advance();
+                    depth += 1;
+                }
+                '*' if self.peek() == '/' => {
+                    self.advance();
+                    depth -= 1;
+                }
+                _ => {}
+            }
+        }
+        true
     }
 
-    fn matches(&mut self, expected: char) -> bool {
+    /// Scans a string literal, or the part of one up to the next `{` if it's interpolated.
+    ///
+    /// `"a {b} c {d} e"` is split into an [`InterpolationStart`] `"a {`, the tokens of
+    /// `b`, an [`InterpolationMiddle`] `} c {`, the tokens of `d` and finally an
+    /// [`InterpolationEnd`] `} e"`. `continuation` is set when scanning the latter two.
+    fn string(&mut self, continuation: bool) -> Token<'src> {
+        let mut literal = String::new();
+        while self.peek() != '"' && !self.is_at_end() {
+            if self.matches('{') {
+                self.interpolations.push(0);
+                let type_ = if continuation {
+                    InterpolationMiddle
+                } else {
+                    InterpolationStart
+                };
+                return self.literal_token(type_, Some(Literal::String(literal)));
+            }
+
+            match self.advance() {
+                '\\' => {
+                    if let Some(escaped) = self.escape() {
+                        literal.push(escaped);
+                    }
+                }
+                ch => literal.push(ch),
+            }
+        }
         if self.is_at_end() {
-            return true;
+            self.error(LexError::UnterminatedString);
+        } else {
+            self.advance();
         }
-        if expected == self.source.get(self.current).copied().unwrap() {
-            self.current += 1;
-            true
+
+        let type_ = if continuation {
+            InterpolationEnd
         } else {
-            false
+            TokenType::String
+        };
+        self.literal_token(type_, Some(Literal::String(literal)))
+    }
+
+    /// Scans a string delimited by `"""`, where the indentation shared by all lines is
+    /// stripped:
+    ///
+    /// ```text
+    /// let text = """
+    ///     first line
+    ///       indented line
+    ///     """;
+    /// ```
+    fn multiline_string(&mut self) -> Token<'src> {
+        struct Line {
+            /// The number of whitespace characters the line starts with.
+            indent: usize,
+            text: String,
+            blank: bool,
+        }
+
+        self.advance();
+        self.advance();
+        while matches!(self.peek(), ' ' | '\t' | '\r') {
+            self.advance();
+        }
+        if !self.matches('\n') {
+            self.error(LexError::MultilineStringStart);
+        }
+
+        let mut lines = Vec::new();
+        let mut text = String::new();
+        let mut indent = 0;
+        let mut blank = true;
+        let mut terminated = false;
+        while !self.is_at_end() {
+            if self.source[self.current..].starts_with("\"\"\"") {
+                self.advance();
+                self.advance();
+                self.advance();
+                terminated = true;
+                break;
+            }
+
+            match self.advance() {
+                '\r' if self.peek() == '\n' => {}
+                '\n' => {
+                    let text = std::mem::take(&mut text);
+                    lines.push(Line {
+                        indent,
+                        text,
+                        blank,
+                    });
+                    indent = 0;
+                    blank = true;
+                }
+                ch @ (' ' | '\t') if blank => {
+                    indent += 1;
+                    text.push(ch);
+                }
+                '\\' => {
+                    blank = false;
+                    if let Some(escaped) = self.escape() {
+                        text.push(escaped);
+                    }
+                }
+                ch => {
+                    blank = false;
+                    text.push(ch);
+                }
+            }
+        }
+        if !terminated {
+            self.error(LexError::UnterminatedMultilineString);
         }
+
+        // the closing `"""` counts towards the indentation when it's on its own line
+        let closing_indent = if blank {
+            Some(indent)
+        } else {
+            lines.push(Line {
+                indent,
+                text,
+                blank,
+            });
+            None
+        };
+        let common_indent = lines
+            .iter()
+            .filter(|line| !line.blank)
+            .map(|line| line.indent)
+            .chain(closing_indent)
+            .min()
+            .unwrap_or(0);
+
+        let literal = lines
+            .iter()
+            .map(|line| {
+                if line.blank {
+                    ""
+                } else {
+                    // the stripped characters are all single-byte whitespace
+                    &line.text[common_indent..]
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.literal_token(TokenType::MultilineString, Some(Literal::String(literal)))
     }
 
-    fn string(&mut self) {
-        while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.increment_line();
+    /// Whether the `r` just consumed starts a raw string like `r"..."` or `r#"..."#`.
+    fn at_raw_string(&self) -> bool {
+        self.source[self.current..]
+            .trim_start_matches('#')
+            .starts_with('"')
+    }
+
+    /// Scans a raw string after its `r`, in which backslashes and quotes have no special
+    /// meaning. As many `#`s as it starts with must follow the closing quote.
+    fn raw_string(&mut self) -> Token<'src> {
+        let mut hashes = 0;
+        while self.matches('#') {
+            hashes += 1;
+        }
+        self.advance();
+
+        let content_start = self.current;
+        loop {
+            if self.is_at_end() {
+                self.error(LexError::UnterminatedRawString);
+                let literal = self.source[content_start..].to_owned();
+                return self.literal_token(TokenType::RawString, Some(Literal::String(literal)));
+            }
+            let content_end = self.current;
+            if self.advance() == '"' {
+                let rest = &self.source[self.current..];
+                if rest.len() >= hashes && rest.bytes().take(hashes).all(|byte| byte == b'#') {
+                    for _ in 0..hashes {
+                        self.advance();
+                    }
+                    let literal = self.source[content_start..content_end].to_owned();
+                    return self
+                        .literal_token(TokenType::RawString, Some(Literal::String(literal)));
+                }
+            }
+        }
+    }
+
+    /// Scans a character literal like `'a'` or `'\n'`.
+    fn char(&mut self) -> Token<'src> {
+        if self.at_label() {
+            while is_identifier_continue(self.peek()) {
+                self.advance();
+            }
+            return self.token(Label);
+        }
+
+        let literal = match self.peek() {
+            '\'' => {
+                self.error(LexError::EmptyChar);
+                None
             }
+            '\n' => {
+                self.error(LexError::UnterminatedChar);
+                return self.literal_token(TokenType::Char, None);
+            }
+            '\\' => {
+                self.advance();
+                self.escape()
+            }
+            _ if self.is_at_end() => {
+                self.error(LexError::UnterminatedChar);
+                return self.literal_token(TokenType::Char, None);
+            }
+            _ => Some(self.advance()),
+        };
+
+        if self.matches('\'') {
+            return self.literal_token(TokenType::Char, literal.map(Literal::Char));
+        }
+        // find the end of a literal like `'ab'` to report it as a whole
+        while !matches!(self.peek(), '\'' | '\n') && !self.is_at_end() {
             self.advance();
         }
+        if self.matches('\'') {
+            self.error(LexError::MultipleChars);
+        } else {
+            self.error(LexError::UnterminatedChar);
+        }
+        self.literal_token(TokenType::Char, None)
+    }
+
+    /// Whether the `'` just consumed starts a loop label rather than a char literal, which
+    /// it does when it's followed by a name that isn't closed by another `'`.
+    fn at_label(&self) -> bool {
+        let rest = &self.source[self.current..];
+        if !rest.starts_with(is_identifier_start) {
+            return false;
+        }
+        let name = rest
+            .char_indices()
+            .skip(1)
+            .find(|&(_, ch)| !is_identifier_continue(ch))
+            .map_or(rest.len(), |(end, _)| end);
+        !rest[name..].starts_with('\'')
+    }
+
+    /// Decodes the escape sequence following a `\`, which was just consumed.
+    fn escape(&mut self) -> Option<char> {
+        // errors point at the `\`, a single character before the current one
+        let backslash = (self.current - 1, self.line, self.column - 1);
         if self.is_at_end() {
-            crate::error(self.line, "unterminated string");
+            self.error_at(LexError::UnterminatedEscape, backslash);
+            return None;
+        }
+
+        match self.advance() {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            '\\' => Some('\\'),
+            '"' => Some('"'),
+            '\'' => Some('\''),
+            '{' => Some('{'),
+            '}' => Some('}'),
+            'u' => self.unicode_escape(backslash),
+            '\n' => {
+                self.error_at(LexError::EscapeAtEndOfLine, backslash);
+                None
+            }
+            other => {
+                self.error_at(LexError::UnknownEscape(other), backslash);
+                None
+            }
+        }
+    }
+
+    /// Decodes the `{XXXX}` part of a `\u{XXXX}` escape.
+    fn unicode_escape(&mut self, backslash: (usize, usize, usize)) -> Option<char> {
+        if self.peek() != '{' {
+            self.error_at(LexError::MissingUnicodeBrace, backslash);
+            return None;
         }
         self.advance();
 
-        let mut literal = String::new();
-        for i in (self.start + 1)..(self.current - 1) {
-            literal.push(self.source[i]);
+        let digits_start = self.current;
+        loop {
+            match self.peek() {
+                '}' => break,
+                '"' | '\n' => {
+                    self.error_at(LexError::UnterminatedUnicodeEscape, backslash);
+                    return None;
+                }
+                _ if self.is_at_end() => {
+                    self.error_at(LexError::UnterminatedUnicodeEscape, backslash);
+                    return None;
+                }
+                ch if ch.is_ascii_hexdigit() => {
+                    self.advance();
+                }
+                ch => {
+                    self.error_at(LexError::InvalidUnicodeDigit(ch), backslash);
+                    // skip the rest of the escape so the string can still be closed
+                    while !matches!(self.peek(), '}' | '"' | '\n') && !self.is_at_end() {
+                        self.advance();
+                    }
+                    self.matches('}');
+                    return None;
+                }
+            }
+        }
+        let digits = &self.source[digits_start..self.current];
+        self.advance();
+
+        if digits.is_empty() {
+            self.error_at(LexError::EmptyUnicodeEscape, backslash);
+            return None;
+        }
+        if digits.len() > 6 {
+            self.error_at(LexError::LongUnicodeEscape, backslash);
+            return None;
         }
 
-        let literal = Literal::String(literal);
-        self.add_full_token(TokenType::String, Some(literal));
+        let decoded = u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(char::from_u32);
+        if decoded.is_none() {
+            self.error_at(LexError::InvalidUnicodeScalar(digits.to_owned()), backslash);
+        }
+        decoded
     }
 
-    fn number(&mut self) {
-        while self.peek().is_ascii_digit() {
+    fn number(&mut self) -> Token<'src> {
+        let first = self.lexeme();
+        let radix = match self.peek() {
+            'x' if first == "0" => 16,
+            'o' if first == "0" => 8,
+            'b' if first == "0" => 2,
+            _ => 10,
+        };
+        if radix == 10 {
+            self.decimal_number()
+        } else {
             self.advance();
+            self.radix_number(radix)
         }
+    }
+
+    fn decimal_number(&mut self) -> Token<'src> {
+        self.digits(10);
+        let mut is_float = false;
         if self.peek() == '.' && self.peek_next().is_ascii_digit() {
+            is_float = true;
             self.advance();
-            while self.peek().is_ascii_digit() {
+            self.digits(10);
+        }
+        if matches!(self.peek(), 'e' | 'E') {
+            let sign = matches!(self.peek_next(), '+' | '-');
+            if self.peek_nth(1 + usize::from(sign)).is_ascii_digit() {
+                is_float = true;
                 self.advance();
+                if sign {
+                    self.advance();
+                }
+                self.digits(10);
             }
         }
 
-        let mut literal = String::new();
-        for i in self.start..self.current {
-            literal.push(self.source[i]);
+        let number = self.lexeme().replace('_', "");
+        let Some(suffix) = self.number_suffix() else {
+            return self.literal_token(Number, None);
+        };
+
+        let literal = if is_float || suffix.is_some_and(NumberSuffix::is_float) {
+            self.float_literal(&number, suffix)
+        } else {
+            self.int_literal(&number, 10, suffix)
+        };
+        self.literal_token(Number, literal)
+    }
+
+    /// Scans the digits of a `0x`, `0o` or `0b` literal, whose prefix was already consumed.
+    fn radix_number(&mut self, radix: u32) -> Token<'src> {
+        let digits_start = self.current;
+        // decimal digits are consumed for every radix so `0b102` is reported as a bad digit
+        self.digits(radix.max(10));
+        let digits = self.source[digits_start..self.current].replace('_', "");
+        let Some(suffix) = self.number_suffix() else {
+            return self.literal_token(Number, None);
+        };
+
+        let base = match radix {
+            16 => "hexadecimal",
+            8 => "octal",
+            _ => "binary",
+        };
+        if digits.is_empty() {
+            self.error(LexError::MissingDigits(base));
+            return self.literal_token(Number, None);
+        }
+        if let Some(digit) = digits.chars().find(|ch| !ch.is_digit(radix)) {
+            self.error(LexError::InvalidDigit(digit, base));
+            return self.literal_token(Number, None);
+        }
+        if suffix.is_some_and(NumberSuffix::is_float) {
+            self.error(LexError::RadixFloat(base));
+            return self.literal_token(Number, None);
         }
 
-        let literal = Literal::Number(f64::from_str(&literal).unwrap());
-        self.add_full_token(Number, Some(literal));
+        let literal = self.int_literal(&digits, radix, suffix);
+        self.literal_token(Number, literal)
     }
 
-    fn identifier(&mut self) {
-        while is_alphanumeric(self.peek()) {
+    /// Consumes digits of the given radix, allowing `_` separators.
+    fn digits(&mut self, radix: u32) {
+        while self.peek().is_digit(radix) || self.peek() == '_' {
             self.advance();
         }
-        let mut text = String::new();
-        for i in self.start..self.current {
-            text.push(self.source[i]);
+    }
+
+    /// Consumes a type suffix like `u8` or `f32`.
+    ///
+    /// Returns `None` if the suffix is invalid, and `Some(None)` if there isn't one.
+    fn number_suffix(&mut self) -> Option<Option<NumberSuffix>> {
+        let suffix_start = self.current;
+        while is_identifier_continue(self.peek()) {
+            self.advance();
+        }
+        if suffix_start == self.current {
+            return Some(None);
+        }
+
+        let suffix = &self.source[suffix_start..self.current];
+        let parsed = NumberSuffix::parse(suffix);
+        if parsed.is_none() {
+            self.error(LexError::InvalidSuffix(suffix.to_owned()));
+        }
+        parsed.map(Some)
+    }
+
+    fn int_literal(
+        &mut self,
+        digits: &str,
+        radix: u32,
+        suffix: Option<NumberSuffix>,
+    ) -> Option<Literal> {
+        let max = suffix
+            .and_then(NumberSuffix::int_max)
+            .unwrap_or(i64::MAX as u128);
+        match u128::from_str_radix(digits, radix) {
+            Ok(value) if value <= max => Some(Literal::Int(value, suffix)),
+            _ => {
+                let r#type = suffix.unwrap_or(NumberSuffix::I64);
+                self.error(LexError::IntOutOfRange(r#type));
+                None
+            }
+        }
+    }
+
+    fn float_literal(&mut self, number: &str, suffix: Option<NumberSuffix>) -> Option<Literal> {
+        if let Some(suffix) = suffix.filter(|suffix| !suffix.is_float()) {
+            self.error(LexError::IntSuffixOnFloat(suffix));
+            return None;
+        }
+        let value = f64::from_str(number).ok()?;
+        let out_of_range = if suffix == Some(NumberSuffix::F32) {
+            (value as f32).is_infinite()
+        } else {
+            value.is_infinite()
+        };
+        if out_of_range {
+            let r#type = suffix.unwrap_or(NumberSuffix::F64);
+            self.error(LexError::FloatOutOfRange(r#type));
+            return None;
+        }
+        Some(Literal::Float(value, suffix))
+    }
+
+    fn identifier(&mut self) -> Token<'src> {
+        while is_identifier_continue(self.peek()) {
+            self.advance();
         }
         let type_ = KEYWORDS
-            .get(&text)
+            .get(self.lexeme())
             .map_or_else(|| Identifier, clone::Clone::clone);
-        self.add_token(type_);
+        let token = self.token(type_);
+
+        if token.r#type == Identifier {
+            if let Some(message) = self.confusables.check(&token) {
+                self.warnings.push(LexWarning {
+                    message,
+                    span: token.span,
+                    line: token.line,
+                    column: token.column,
+                });
+            }
+        }
+        token
     }
+}
 
-    fn increment_line(&mut self) {
-        self.line += 1;
+impl<'src> Iterator for Lexer<'src> {
+    type Item = Token<'src>;
+
+    fn next(&mut self) -> Option<Token<'src>> {
+        self.skip_trivia();
+        if self.is_at_end() {
+            if self.finished {
+                return None;
+            }
+            self.finished = true;
+            self.start_token();
+            let mut eof = self.token(Eof);
+            eof.leading_trivia = std::mem::take(&mut self.pending_trivia);
+            return Some(eof);
+        }
+
+        self.start_token();
+        let mut token = self.scan_token();
+        token.error = self.error.take().map(Box::new);
+        token.leading_trivia = std::mem::take(&mut self.pending_trivia);
+        if self.lossless {
+            self.trailing_trivia(&mut token);
+        }
+        Some(token)
+    }
+}
+
+/// Whether `ch` can start an identifier, following UAX #31.
+fn is_identifier_start(ch: char) -> bool {
+    ch == '_' || ch.is_xid_start()
+}
+
+pub(super) fn is_identifier_continue(ch: char) -> bool {
+    ch.is_xid_continue()
+}
+
+/// Tracks the identifiers of a file to warn about ones that look alike, like the latin
+/// `a` and the cyrillic `а`, following the confusable detection of UTS #39.
+#[derive(Clone, Default)]
+struct Confusables<'src> {
+    seen: HashSet<&'src str>,
+    /// Maps the skeletons of identifiers to their names, only built once the first
+    /// non-ASCII identifier shows up since ASCII identifiers aren't checked among each other.
+    skeletons: Option<HashMap<String, Cow<'src, str>>>,
+}
+
+impl<'src> Confusables<'src> {
+    /// Returns a warning if the identifier could be mistaken for another one.
+    fn check(&mut self, token: &Token<'src>) -> Option<String> {
+        if !self.seen.insert(token.lexeme) {
+            return None;
+        }
+        let skeletons = match &mut self.skeletons {
+            Some(skeletons) => skeletons,
+            None if token.lexeme.is_ascii() => return None,
+            None => self.skeletons.insert(
+                self.seen
+                    .iter()
+                    .filter(|name| name.is_ascii())
+                    .map(|name| (skeleton(name).collect(), Cow::Borrowed(*name)))
+                    .collect(),
+            ),
+        };
+
+        let name = token.name();
+        if !name.is_ascii() && !name.as_ref().is_single_script() {
+            return Some(format!(
+                "identifier `{name}` mixes characters from different scripts"
+            ));
+        }
+        match skeletons.get(&skeleton(&name).collect::<String>()) {
+            Some(other) if *other != name && !(other.is_ascii() && name.is_ascii()) => Some(
+                format!("identifier `{name}` looks like `{other}`, but they are different"),
+            ),
+            Some(_) => None,
+            None => {
+                skeletons.insert(skeleton(&name).collect(), name);
+                None
+            }
+        }
     }
 }
 
-const fn is_alphanumeric(ch: char) -> bool {
-    ch.is_ascii_alphanumeric() || ch == '_'
+#[cfg(test)]
+pub mod tests {
+    use super::{Confusables, Lexer};
+    use crate::ast::{LexError, LineIndex, Literal, NumberSuffix, Span, TokenType};
+
+    #[test]
+    fn tokens_carry_spans() {
+        let source = "let s = \"ö\" + 1;\n  \"a\nb\" x";
+        let tokens = Lexer::new(source).scan_tokens();
+
+        let plus = &tokens[4];
+        assert_eq!(plus.r#type, TokenType::Plus);
+        assert_eq!(&source[plus.span.start..plus.span.end], "+");
+        assert_eq!((plus.line, plus.column), (1, 13));
+
+        let string = &tokens[7];
+        assert_eq!(string.span, Span::new(20, 25));
+        assert_eq!((string.line, string.column), (2, 3));
+
+        let x = &tokens[8];
+        assert_eq!((x.line, x.column), (3, 4));
+        assert_eq!(LineIndex::new(source).line_col(x.span.start), (3, 4));
+    }
+
+    #[test]
+    fn string_escapes() {
+        let source = r#""a\n\t\"b\"\\\0\u{1F600}\u{e9}" "\q\u{110000}x""#;
+        let tokens = Lexer::new(source).scan_tokens();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(
+            tokens[0].literal,
+            Some(Literal::String("a\n\t\"b\"\\\0😀é".to_owned()))
+        );
+        // the first problem of a malformed literal is kept, reported at its `\`
+        assert_eq!(tokens[1].r#type, TokenType::String);
+        let error = tokens[1].error.as_deref().unwrap();
+        assert_eq!(error.error, LexError::UnknownEscape('q'));
+        assert_eq!((error.span, error.column), (Span::new(33, 35), 34));
+    }
+
+    #[test]
+    fn escape_errors_in_interpolations() {
+        let source = "\"a\\q {x} b\";";
+        let tokens = Lexer::new(source).scan_tokens();
+        let types: Vec<_> = tokens.iter().map(|token| token.r#type.clone()).collect();
+
+        assert_eq!(
+            types,
+            [
+                TokenType::InterpolationStart,
+                TokenType::Identifier,
+                TokenType::InterpolationEnd,
+                TokenType::Semicolon,
+                TokenType::Eof,
+            ]
+        );
+        let error = tokens[0].error.as_deref().unwrap();
+        assert_eq!(error.error, LexError::UnknownEscape('q'));
+        assert_eq!((error.line, error.column), (1, 3));
+        assert!(tokens[1..].iter().all(|token| token.error.is_none()));
+    }
+
+    #[test]
+    fn numeric_literals() {
+        let source = "42 1_000_000 0xff_u8 0o17 0b1010 1e-9 2.5f32 10u8 3f64 1.max 300u8 0b12";
+        let tokens = Lexer::new(source).scan_tokens();
+        let literals: Vec<_> = tokens
+            .iter()
+            .filter(|token| token.r#type == TokenType::Number && token.error.is_none())
+            .map(|token| token.literal.clone())
+            .collect();
+        let errors: Vec<_> = tokens
+            .iter()
+            .filter_map(|token| Some(token.error.as_deref()?.error.clone()))
+            .collect();
+
+        assert_eq!(
+            literals,
+            [
+                Some(Literal::Int(42, None)),
+                Some(Literal::Int(1_000_000, None)),
+                Some(Literal::Int(255, Some(NumberSuffix::U8))),
+                Some(Literal::Int(15, None)),
+                Some(Literal::Int(10, None)),
+                Some(Literal::Float(1e-9, None)),
+                Some(Literal::Float(2.5, Some(NumberSuffix::F32))),
+                Some(Literal::Int(10, Some(NumberSuffix::U8))),
+                Some(Literal::Float(3.0, Some(NumberSuffix::F64))),
+                Some(Literal::Int(1, None)),
+            ]
+        );
+        assert_eq!(
+            errors,
+            [
+                LexError::IntOutOfRange(NumberSuffix::U8),
+                LexError::InvalidDigit('2', "binary"),
+            ]
+        );
+    }
+
+    #[test]
+    fn lexes_on_demand_without_copying() {
+        let source = "let größe = 1;";
+        let mut lexer = Lexer::new(source);
+
+        let keyword = lexer.next().unwrap();
+        assert_eq!(keyword.r#type, TokenType::Let);
+        assert!(std::ptr::eq(keyword.lexeme, &source[..3]));
+
+        assert_eq!(lexer.by_ref().last().unwrap().r#type, TokenType::Eof);
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn unicode_identifiers() {
+        let source = "let größe = 変数 + _x1 + gro\u{308}ße;";
+        let tokens = Lexer::new(source).scan_tokens();
+        let types: Vec<_> = tokens.iter().map(|token| token.r#type.clone()).collect();
+
+        assert_eq!(
+            types,
+            [
+                TokenType::Let,
+                TokenType::Identifier,
+                TokenType::Equal,
+                TokenType::Identifier,
+                TokenType::Plus,
+                TokenType::Identifier,
+                TokenType::Plus,
+                TokenType::Identifier,
+                TokenType::Semicolon,
+                TokenType::Eof
+            ]
+        );
+        assert_ne!(tokens[1].lexeme, tokens[7].lexeme);
+        assert_eq!(tokens[1].name(), tokens[7].name());
+    }
+
+    #[test]
+    fn confusable_identifiers() {
+        let mut confusables = Confusables::default();
+        let tokens = Lexer::new("paypal раураl pаypal paypal").scan_tokens();
+
+        assert_eq!(confusables.check(&tokens[0]), None);
+        assert!(confusables.check(&tokens[1]).is_some());
+        assert!(confusables.check(&tokens[2]).is_some());
+        assert_eq!(confusables.check(&tokens[3]), None);
+
+        let mut lexer = Lexer::new("paypal раураl pаypal paypal");
+        lexer.by_ref().for_each(drop);
+        let columns: Vec<_> = lexer
+            .warnings()
+            .iter()
+            .map(|warning| warning.column)
+            .collect();
+        assert_eq!(columns, [8, 15]);
+    }
+
+    #[test]
+    fn char_raw_and_multiline_literals() {
+        let source = "'a' '\\n' '\\'' '_' 'outer r\"C:\\path\" r##\"say \"#hi\"#\"## \"\"\"\n    first\n\n      second \\t\n    \"\"\"";
+        let tokens = Lexer::new(source).scan_tokens();
+        let literals: Vec<_> = tokens
+            .iter()
+            .map(|token| (token.r#type.clone(), token.literal.clone()))
+            .collect();
+
+        assert_eq!(
+            literals,
+            [
+                (TokenType::Char, Some(Literal::Char('a'))),
+                (TokenType::Char, Some(Literal::Char('\n'))),
+                (TokenType::Char, Some(Literal::Char('\''))),
+                (TokenType::Char, Some(Literal::Char('_'))),
+                (TokenType::Label, None),
+                (
+                    TokenType::RawString,
+                    Some(Literal::String("C:\\path".to_owned()))
+                ),
+                (
+                    TokenType::RawString,
+                    Some(Literal::String("say \"#hi\"#".to_owned()))
+                ),
+                (
+                    TokenType::MultilineString,
+                    Some(Literal::String("first\n\n  second \t".to_owned()))
+                ),
+                (TokenType::Eof, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn string_interpolation() {
+        let source = r#""hi {name}, {f({a: 1}) + 1}!" "\{x\}""#;
+        let types: Vec<_> = Lexer::new(source)
+            .map(|token| (token.r#type, token.lexeme))
+            .collect();
+
+        assert_eq!(
+            types,
+            [
+                (TokenType::InterpolationStart, "\"hi {"),
+                (TokenType::Identifier, "name"),
+                (TokenType::InterpolationMiddle, "}, {"),
+                (TokenType::Identifier, "f"),
+                (TokenType::LeftParen, "("),
+                (TokenType::LeftBrace, "{"),
+                (TokenType::Identifier, "a"),
+                (TokenType::Colon, ":"),
+                (TokenType::Number, "1"),
+                (TokenType::RightBrace, "}"),
+                (TokenType::RightParen, ")"),
+                (TokenType::Plus, "+"),
+                (TokenType::Number, "1"),
+                (TokenType::InterpolationEnd, "}!\""),
+                (TokenType::String, "\"\\{x\\}\""),
+                (TokenType::Eof, ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn error_tokens() {
+        let source = "a @ \"open\n/* never closed";
+        let tokens: Vec<_> = Lexer::new(source)
+            .map(|token| {
+                (
+                    token.r#type,
+                    token.span,
+                    token.error.map(|error| error.error),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            tokens,
+            [
+                (TokenType::Identifier, Span::new(0, 1), None),
+                (
+                    TokenType::Error,
+                    Span::new(2, 3),
+                    Some(LexError::UnexpectedCharacter('@'))
+                ),
+                (
+                    TokenType::String,
+                    Span::new(4, 25),
+                    Some(LexError::UnterminatedString)
+                ),
+                (TokenType::Eof, Span::new(25, 25), None),
+            ]
+        );
+
+        let tokens = Lexer::new("1 /* open").lossless().scan_tokens();
+        assert_eq!(tokens[1].r#type, TokenType::Error);
+        assert_eq!(
+            tokens[1].error.as_deref().map(|error| &error.error),
+            Some(&LexError::UnterminatedBlockComment)
+        );
+        assert_eq!(tokens[1].lexeme, "/* open");
+
+        // a NUL byte is an unexpected character, not the end of the file
+        let types: Vec<_> = Lexer::new("a\0b").map(|token| token.r#type).collect();
+        assert_eq!(
+            types,
+            [
+                TokenType::Identifier,
+                TokenType::Error,
+                TokenType::Identifier,
+                TokenType::Eof
+            ]
+        );
+    }
 }
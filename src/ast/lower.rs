@@ -0,0 +1,307 @@
+use super::{Expr, ForClauses, Literal, Part, Pattern, Span, Stmt, Token, TokenType};
+
+/// Desugars the constructs of a checked program that only exist to be convenient to
+/// write into simpler ones, so later stages don't need to know about them:
+///
+/// - A C-style `for` loop becomes a `while` loop in a block with its initializer.
+/// - A `for pattern in iterable` loop follows the iterator protocol: it calls `iter()`
+///   on the iterable once, then binds `next()` of what that returns to the pattern for
+///   as long as its `has_next()` is true.
+pub fn lower(statements: &mut [Stmt<'_>]) {
+    for stmt in statements {
+        statement(stmt);
+    }
+}
+
+fn statement(stmt: &mut Stmt<'_>) {
+    match stmt {
+        Stmt::Expression(expr, _) => self::expr(expr),
+        Stmt::Let { initializer, .. } => {
+            if let Some(initializer) = initializer {
+                self::expr(initializer);
+            }
+        }
+        Stmt::Return(_, value, _) | Stmt::Break(_, _, value, _) => {
+            if let Some(value) = value {
+                self::expr(value);
+            }
+        }
+        Stmt::While {
+            condition,
+            body,
+            increment,
+            ..
+        } => {
+            self::expr(condition);
+            statement(body);
+            if let Some(increment) = increment {
+                self::expr(increment);
+            }
+        }
+        Stmt::For { span, .. } => {
+            let span = *span;
+            let Stmt::For {
+                label,
+                keyword,
+                clauses,
+                body,
+                span,
+            } = std::mem::replace(stmt, block(Vec::new(), span))
+            else {
+                unreachable!("the statement was just matched as a `for` loop");
+            };
+            *stmt = match *clauses {
+                ForClauses::CStyle {
+                    initializer,
+                    condition,
+                    increment,
+                } => {
+                    let condition =
+                        condition.unwrap_or(Expr::Literal(Literal::Bool(true), keyword.span));
+                    let mut lowered = vec![Stmt::While {
+                        label,
+                        condition,
+                        body,
+                        increment,
+                        span,
+                    }];
+                    if let Some(initializer) = initializer {
+                        lowered.insert(0, *initializer);
+                    }
+                    block(lowered, span)
+                }
+                ForClauses::In { pattern, iterable } => {
+                    // `$` can't be in an identifier, so the iterator can't clash with a name
+                    // in the program
+                    let iterator = synthetic(TokenType::Identifier, "$iterator", &keyword);
+                    let next = Stmt::Let {
+                        keyword: synthetic(TokenType::Let, "let", &keyword),
+                        pattern,
+                        ty: None,
+                        initializer: Some(method_call(variable(&iterator), "next", &keyword)),
+                        docs: Vec::new(),
+                        span,
+                    };
+                    let has_next = method_call(variable(&iterator), "has_next", &keyword);
+                    let iterator_span = iterator.span;
+                    block(
+                        vec![
+                            Stmt::Let {
+                                keyword: synthetic(TokenType::Let, "let", &keyword),
+                                pattern: Pattern::Binding(iterator, iterator_span),
+                                ty: None,
+                                initializer: Some(method_call(iterable, "iter", &keyword)),
+                                docs: Vec::new(),
+                                span,
+                            },
+                            Stmt::While {
+                                label,
+                                condition: has_next,
+                                body: Box::new(block(vec![next, *body], span)),
+                                increment: None,
+                                span,
+                            },
+                        ],
+                        span,
+                    )
+                }
+            };
+            // the parts of the loop may hold more loops to lower
+            statement(stmt);
+        }
+        Stmt::Fn { params, body, .. } => {
+            for default in params.iter_mut().filter_map(|param| param.default.as_mut()) {
+                self::expr(default);
+            }
+            self::expr(body);
+        }
+        Stmt::Continue(..) | Stmt::Struct { .. } | Stmt::Enum { .. } => {}
+    }
+}
+
+fn expr(expr: &mut Expr<'_>) {
+    match expr {
+        Expr::Array(elements, _) | Expr::Tuple(elements, _) => {
+            for element in elements {
+                self::expr(element);
+            }
+        }
+        Expr::ArrayRepeat(left, right, _)
+        | Expr::Binary(left, _, right, _)
+        | Expr::CompoundSet(left, _, _, right, _)
+        | Expr::Index(left, right, _)
+        | Expr::Logical(left, _, right, _)
+        | Expr::Set(left, _, right, _) => {
+            self::expr(left);
+            self::expr(right);
+        }
+        Expr::Assign(_, value, _) | Expr::CompoundAssign(_, _, value, _) => self::expr(value),
+        Expr::Block(statements, tail, _) => {
+            lower(statements);
+            if let Some(tail) = tail {
+                self::expr(tail);
+            }
+        }
+        Expr::Call { callee, args, .. } => {
+            self::expr(callee);
+            for arg in args {
+                self::expr(arg);
+            }
+        }
+        Expr::Cast(inner, ..)
+        | Expr::Get(inner, ..)
+        | Expr::Grouping(inner, _)
+        | Expr::Loop { body: inner, .. }
+        | Expr::Try(inner, _)
+        | Expr::Unary(_, inner, _) => self::expr(inner),
+        Expr::If {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            self::expr(condition);
+            self::expr(then_branch);
+            if let Some(else_branch) = else_branch {
+                self::expr(else_branch);
+            }
+        }
+        Expr::CompoundIndexSet(object, index, _, value, _)
+        | Expr::IndexSet(object, index, value, _) => {
+            self::expr(object);
+            self::expr(index);
+            self::expr(value);
+        }
+        Expr::Interpolation(parts, _) => {
+            for part in parts {
+                if let Part::Expr(inner) = part {
+                    self::expr(inner);
+                }
+            }
+        }
+        Expr::Lambda { params, body, .. } => {
+            for default in params.iter_mut().filter_map(|param| param.default.as_mut()) {
+                self::expr(default);
+            }
+            self::expr(body);
+        }
+        Expr::Match {
+            scrutinee, arms, ..
+        } => {
+            self::expr(scrutinee);
+            for arm in arms {
+                if let Some(guard) = &mut arm.guard {
+                    self::expr(guard);
+                }
+                self::expr(&mut arm.body);
+            }
+        }
+        Expr::Range(start, _, end, _) => {
+            for inner in [start, end].into_iter().flatten() {
+                self::expr(inner);
+            }
+        }
+        Expr::StructLiteral(_, fields, _) => {
+            for (_, value) in fields {
+                self::expr(value);
+            }
+        }
+        Expr::Literal(..) | Expr::Path(..) | Expr::Variable(..) => {}
+    }
+}
+
+/// A block statement of `statements`, without a value.
+fn block<'src>(statements: Vec<Stmt<'src>>, span: Span) -> Stmt<'src> {
+    Stmt::Expression(Expr::Block(statements, None, span), span)
+}
+
+/// A token the program doesn't contain, reported at `at` if anything goes wrong with it.
+fn synthetic<'src>(r#type: TokenType, lexeme: &'static str, at: &Token<'src>) -> Token<'src> {
+    Token::new(r#type, lexeme, None, at.span, at.line, at.column)
+}
+
+fn variable<'src>(name: &Token<'src>) -> Expr<'src> {
+    Expr::Variable(name.clone(), name.span)
+}
+
+/// Calls the method `name` of `object` without arguments.
+fn method_call<'src>(object: Expr<'src>, name: &'static str, at: &Token<'src>) -> Expr<'src> {
+    let span = object.span();
+    let callee = Expr::Get(
+        Box::new(object),
+        synthetic(TokenType::Identifier, name, at),
+        span,
+    );
+    Expr::Call {
+        callee: Box::new(callee),
+        args: Vec::new(),
+        paren: synthetic(TokenType::RightParen, ")", at),
+        span,
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::lower;
+    use crate::ast::{lexer::Lexer, parser::Parser, Expr, Pattern, Stmt};
+
+    /// The statements of a block statement without a value.
+    fn block<'a, 'src>(stmt: &'a Stmt<'src>) -> &'a [Stmt<'src>] {
+        let Stmt::Expression(Expr::Block(statements, None, _), _) = stmt else {
+            panic!("{stmt:?} is not a block")
+        };
+        statements
+    }
+
+    #[test]
+    fn lowers_for_loops() {
+        let mut ast = Parser::new(Lexer::new(
+            "
+            for (let i = 0; i < 3; i += 1) { continue; }
+            'outer: for (x, y) in pairs { for z in x {} }
+        ",
+        ))
+        .parse();
+        lower(&mut ast);
+
+        // the increment stays separate from the body so `continue` still runs it
+        let statements = block(&ast[0]);
+        assert!(matches!(statements[0], Stmt::Let { .. }));
+        assert!(matches!(
+            statements[1],
+            Stmt::While {
+                increment: Some(Expr::CompoundAssign(..)),
+                ..
+            }
+        ));
+
+        let [Stmt::Let {
+            pattern: Pattern::Binding(iterator, _),
+            initializer: Some(Expr::Call { callee, .. }),
+            ..
+        }, Stmt::While {
+            label: Some(label),
+            condition: Expr::Call { .. },
+            body,
+            ..
+        }] = block(&ast[1])
+        else {
+            panic!()
+        };
+        assert_eq!(iterator.lexeme, "$iterator");
+        assert!(matches!(callee.as_ref(), Expr::Get(_, name, _) if name.lexeme == "iter"));
+        assert_eq!(label.lexeme, "'outer");
+
+        // each iteration binds the pattern first, and the inner loop is lowered too
+        let body = block(body);
+        assert!(matches!(
+            &body[0],
+            Stmt::Let {
+                pattern: Pattern::Tuple(..),
+                ..
+            }
+        ));
+        let inner = block(&body[1]);
+        assert!(matches!(block(&inner[0])[1], Stmt::While { .. }));
+    }
+}
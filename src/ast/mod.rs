@@ -1,28 +1,383 @@
+pub mod captures;
+pub mod checker;
+pub mod cst;
+pub mod incremental;
 pub mod lexer;
+pub mod lower;
+pub mod operators;
 pub mod parser;
+pub mod span;
 pub mod tokens;
 
-pub use tokens::{Literal, Token, TokenType};
+pub use span::{LineIndex, Span};
+pub use tokens::{
+    LexError, LexWarning, Literal, NumberSuffix, Token, TokenError, TokenType, Trivia, TriviaKind,
+};
 
 #[derive(PartialEq, Clone, Debug)]
 #[non_exhaustive]
-pub enum Stmt {
-    Block(Vec<Stmt>),
-    Expression(Expr),
-    If(Expr, Box<Stmt>, Box<Option<Stmt>>),
-    Let(Token, Option<Expr>),
-    While(Expr, Box<Stmt>),
-    Fn(Token, Vec<String>, Box<Stmt>),
+pub enum Stmt<'src> {
+    Expression(Expr<'src>, Span),
+    Let {
+        keyword: Token<'src>,
+        pattern: Pattern<'src>,
+        ty: Option<TypeExpr<'src>>,
+        initializer: Option<Expr<'src>>,
+        docs: Vec<Token<'src>>,
+        span: Span,
+    },
+    Return(Token<'src>, Option<Expr<'src>>, Span),
+    While {
+        label: Option<Token<'src>>,
+        condition: Expr<'src>,
+        body: Box<Stmt<'src>>,
+        /// Runs after every iteration, including the ones ended by `continue`, for the
+        /// increment of a desugared `for` loop.
+        increment: Option<Expr<'src>>,
+        span: Span,
+    },
+    /// A `for` loop as it's written, which [`lower`] desugars into a `while` loop.
+    For {
+        label: Option<Token<'src>>,
+        keyword: Token<'src>,
+        clauses: Box<ForClauses<'src>>,
+        body: Box<Stmt<'src>>,
+        span: Span,
+    },
+    /// `break`, with an optional label and, when it ends a `loop`, an optional value.
+    Break(Token<'src>, Option<Token<'src>>, Option<Expr<'src>>, Span),
+    /// `continue`, with an optional label.
+    Continue(Token<'src>, Option<Token<'src>>, Span),
+    Fn {
+        name: Token<'src>,
+        params: Vec<Param<'src>>,
+        return_type: Option<TypeExpr<'src>>,
+        body: Box<Expr<'src>>,
+        docs: Vec<Token<'src>>,
+        span: Span,
+    },
+    Struct {
+        name: Token<'src>,
+        fields: Vec<Field<'src>>,
+        docs: Vec<Token<'src>>,
+        span: Span,
+    },
+    Enum {
+        name: Token<'src>,
+        variants: Vec<Variant<'src>>,
+        docs: Vec<Token<'src>>,
+        span: Span,
+    },
+}
+
+impl Stmt<'_> {
+    #[must_use]
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::Expression(.., span)
+            | Self::Let { span, .. }
+            | Self::Return(.., span)
+            | Self::While { span, .. }
+            | Self::For { span, .. }
+            | Self::Break(.., span)
+            | Self::Continue(.., span)
+            | Self::Fn { span, .. }
+            | Self::Struct { span, .. }
+            | Self::Enum { span, .. } => *span,
+        }
+    }
 }
 
 #[derive(PartialEq, Clone, Debug)]
 #[non_exhaustive]
-pub enum Expr {
-    Assign(Token, Box<Expr>),
-    Binary(Box<Expr>, Token, Box<Expr>),
-    Grouping(Box<Expr>),
-    Literal(Literal),
-    Logical(Box<Expr>, Token, Box<Expr>),
-    Unary(Token, Box<Expr>),
-    Variable(Token),
+pub enum Expr<'src> {
+    /// `[1, 2, 3]`
+    Array(Vec<Expr<'src>>, Span),
+    /// `[value; count]`
+    ArrayRepeat(Box<Expr<'src>>, Box<Expr<'src>>, Span),
+    Assign(Token<'src>, Box<Expr<'src>>, Span),
+    Binary(Box<Expr<'src>>, Token<'src>, Box<Expr<'src>>, Span),
+    /// `{ statements; tail }`, whose value is that of the tail expression.
+    Block(Vec<Stmt<'src>>, Option<Box<Expr<'src>>>, Span),
+    Call {
+        callee: Box<Expr<'src>>,
+        args: Vec<Expr<'src>>,
+        /// The closing parenthesis, where errors of the call are reported.
+        paren: Token<'src>,
+        span: Span,
+    },
+    Cast(Box<Expr<'src>>, TypeExpr<'src>, Span),
+    CompoundAssign(Token<'src>, Token<'src>, Box<Expr<'src>>, Span),
+    /// `object[index] += value`
+    CompoundIndexSet(
+        Box<Expr<'src>>,
+        Box<Expr<'src>>,
+        Token<'src>,
+        Box<Expr<'src>>,
+        Span,
+    ),
+    /// `object.name += value`
+    CompoundSet(
+        Box<Expr<'src>>,
+        Token<'src>,
+        Token<'src>,
+        Box<Expr<'src>>,
+        Span,
+    ),
+    /// `object.name`
+    Get(Box<Expr<'src>>, Token<'src>, Span),
+    Grouping(Box<Expr<'src>>, Span),
+    /// `if condition { ... } else { ... }`, whose value is that of the branch taken, or
+    /// nil when the condition is false and there's no `else`.
+    If {
+        condition: Box<Expr<'src>>,
+        then_branch: Box<Expr<'src>>,
+        else_branch: Option<Box<Expr<'src>>>,
+        span: Span,
+    },
+    /// `object[index]`, or a slice like `object[1..3]` when the index is a range.
+    Index(Box<Expr<'src>>, Box<Expr<'src>>, Span),
+    /// `object[index] = value`
+    IndexSet(Box<Expr<'src>>, Box<Expr<'src>>, Box<Expr<'src>>, Span),
+    Interpolation(Vec<Part<'src>>, Span),
+    Lambda {
+        params: Vec<Param<'src>>,
+        return_type: Option<TypeExpr<'src>>,
+        body: Box<Expr<'src>>,
+        /// The variables of enclosing functions the body refers to, filled in by
+        /// [`captures::analyze`].
+        captures: Vec<Token<'src>>,
+        span: Span,
+    },
+    Literal(Literal, Span),
+    Logical(Box<Expr<'src>>, Token<'src>, Box<Expr<'src>>, Span),
+    /// `loop { ... }`, an infinite loop whose value is given by the `break` that ends it.
+    Loop {
+        label: Option<Token<'src>>,
+        body: Box<Expr<'src>>,
+        span: Span,
+    },
+    Match {
+        keyword: Token<'src>,
+        scrutinee: Box<Expr<'src>>,
+        arms: Vec<Arm<'src>>,
+        span: Span,
+    },
+    Path(Vec<Token<'src>>, Span),
+    Range(
+        Option<Box<Expr<'src>>>,
+        Token<'src>,
+        Option<Box<Expr<'src>>>,
+        Span,
+    ),
+    /// `object.name = value`
+    Set(Box<Expr<'src>>, Token<'src>, Box<Expr<'src>>, Span),
+    /// `Point { x: 1, y }`, where a field without a value takes the variable of its name.
+    StructLiteral(Token<'src>, Vec<(Token<'src>, Expr<'src>)>, Span),
+    Try(Box<Expr<'src>>, Span),
+    /// `(a, b)`, or `()`. A single element needs a trailing comma, `(a,)`, to not just be
+    /// a [`Expr::Grouping`].
+    Tuple(Vec<Expr<'src>>, Span),
+    Unary(Token<'src>, Box<Expr<'src>>, Span),
+    Variable(Token<'src>, Span),
+}
+
+impl Expr<'_> {
+    #[must_use]
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::Array(.., span)
+            | Self::ArrayRepeat(.., span)
+            | Self::Assign(.., span)
+            | Self::Binary(.., span)
+            | Self::Block(.., span)
+            | Self::Call { span, .. }
+            | Self::Cast(.., span)
+            | Self::CompoundAssign(.., span)
+            | Self::CompoundIndexSet(.., span)
+            | Self::CompoundSet(.., span)
+            | Self::Get(.., span)
+            | Self::Grouping(.., span)
+            | Self::If { span, .. }
+            | Self::Index(.., span)
+            | Self::IndexSet(.., span)
+            | Self::Interpolation(.., span)
+            | Self::Lambda { span, .. }
+            | Self::Literal(.., span)
+            | Self::Logical(.., span)
+            | Self::Loop { span, .. }
+            | Self::Match { span, .. }
+            | Self::Path(.., span)
+            | Self::Range(.., span)
+            | Self::Set(.., span)
+            | Self::StructLiteral(.., span)
+            | Self::Try(.., span)
+            | Self::Tuple(.., span)
+            | Self::Unary(.., span)
+            | Self::Variable(.., span) => *span,
+        }
+    }
+}
+
+/// A piece of an interpolated string like `"hello {name}"`.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Part<'src> {
+    Text(String),
+    Expr(Box<Expr<'src>>),
+}
+
+/// A function parameter like `count: i32 = 0`.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Param<'src> {
+    pub name: Token<'src>,
+    pub ty: Option<TypeExpr<'src>>,
+    pub default: Option<Expr<'src>>,
+    pub span: Span,
+}
+
+/// A field of a struct declaration, `name: Type`.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Field<'src> {
+    pub name: Token<'src>,
+    pub ty: TypeExpr<'src>,
+    pub span: Span,
+}
+
+/// What a `for` loop iterates over.
+#[derive(PartialEq, Clone, Debug)]
+pub enum ForClauses<'src> {
+    /// `for (let i = 0; i < n; i += 1)`, where each clause can be left out.
+    CStyle {
+        initializer: Option<Box<Stmt<'src>>>,
+        condition: Option<Expr<'src>>,
+        increment: Option<Expr<'src>>,
+    },
+    /// `for pattern in iterable`
+    In {
+        pattern: Pattern<'src>,
+        iterable: Expr<'src>,
+    },
+}
+
+/// A variant of an enum declaration: `Empty`, `Circle(f64)` or `Rect { w: f64, h: f64 }`.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Variant<'src> {
+    pub name: Token<'src>,
+    pub fields: VariantFields<'src>,
+    pub span: Span,
+}
+
+/// The payload an enum variant carries.
+#[derive(PartialEq, Clone, Debug)]
+pub enum VariantFields<'src> {
+    Unit,
+    Tuple(Vec<TypeExpr<'src>>),
+    Struct(Vec<Field<'src>>),
+}
+
+impl VariantFields<'_> {
+    /// How many values the variant carries.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Unit => 0,
+            Self::Tuple(types) => types.len(),
+            Self::Struct(fields) => fields.len(),
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// An arm of a `match` expression, `pattern if guard => body`.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Arm<'src> {
+    pub pattern: Pattern<'src>,
+    pub guard: Option<Expr<'src>>,
+    pub body: Expr<'src>,
+    pub span: Span,
+}
+
+/// The shape a value is compared against, binding the names in it on a match.
+#[derive(PartialEq, Clone, Debug)]
+#[non_exhaustive]
+pub enum Pattern<'src> {
+    /// `1`, `"text"` or `true`
+    Literal(Literal, Span),
+    /// `-1` or `-0.5`, a number with a minus in front.
+    Negative(Literal, Span),
+    /// A name, which matches anything.
+    Binding(Token<'src>, Span),
+    /// `_`
+    Wildcard(Span),
+    /// `(a, b)`
+    Tuple(Vec<Pattern<'src>>, Span),
+    /// `Point { x, y: 0 }` or `Shape::Rect { w, h }`, where a field without a pattern
+    /// binds the name of the field.
+    Struct(Vec<Token<'src>>, Vec<(Token<'src>, Pattern<'src>)>, Span),
+    /// `Shape::Circle(r)`, or `Shape::Empty` for a variant without a payload.
+    Variant(Vec<Token<'src>>, Vec<Pattern<'src>>, Span),
+}
+
+impl<'src> Pattern<'src> {
+    #[must_use]
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::Literal(.., span)
+            | Self::Negative(.., span)
+            | Self::Binding(.., span)
+            | Self::Wildcard(span)
+            | Self::Tuple(.., span)
+            | Self::Struct(.., span)
+            | Self::Variant(.., span) => *span,
+        }
+    }
+
+    /// The names the pattern binds, from left to right.
+    #[must_use]
+    pub fn bindings(&self) -> Vec<&Token<'src>> {
+        match self {
+            Self::Binding(name, _) => vec![name],
+            Self::Literal(..) | Self::Negative(..) | Self::Wildcard(_) => Vec::new(),
+            Self::Tuple(patterns, _) | Self::Variant(_, patterns, _) => {
+                patterns.iter().flat_map(Pattern::bindings).collect()
+            }
+            Self::Struct(_, fields, _) => fields
+                .iter()
+                .flat_map(|(_, pattern)| pattern.bindings())
+                .collect(),
+        }
+    }
+}
+
+/// A type annotation.
+#[derive(PartialEq, Clone, Debug)]
+#[non_exhaustive]
+pub enum TypeExpr<'src> {
+    /// `i32`, or `Map<K, V>` with type arguments
+    Named(Token<'src>, Vec<TypeExpr<'src>>, Span),
+    /// `(i32, f64)`, or `()`
+    Tuple(Vec<TypeExpr<'src>>, Span),
+    /// `[T]`
+    Array(Box<TypeExpr<'src>>, Span),
+    /// `fn(i32, i32) -> i32`, where a function without a return type returns `()`
+    Fn(Vec<TypeExpr<'src>>, Option<Box<TypeExpr<'src>>>, Span),
+    /// `T?`
+    Optional(Box<TypeExpr<'src>>, Span),
+}
+
+impl TypeExpr<'_> {
+    #[must_use]
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::Named(.., span)
+            | Self::Tuple(.., span)
+            | Self::Array(.., span)
+            | Self::Fn(.., span)
+            | Self::Optional(.., span) => *span,
+        }
+    }
 }
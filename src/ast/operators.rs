@@ -0,0 +1,186 @@
+use super::TokenType::{
+    self, Amp, And, As, Bang, BangEqual, Caret, Dot, DotDot, DotDotEqual, Equal, EqualEqual,
+    Greater, GreaterEqual, GreaterGreater, LeftBracket, LeftParen, Less, LessEqual, LessLess,
+    Minus, MinusEqual, Or, Percent, PercentEqual, Pipe, Plus, PlusEqual, Question, Slash,
+    SlashEqual, Star, StarEqual, Tilde,
+};
+
+/// Every operator of the language. The expression parser is driven entirely by this
+/// table, so adding an operator only takes a new entry (and an AST node to build, if
+/// none of the existing [`OperatorKind`]s fit).
+#[rustfmt::skip]
+pub static OPERATORS: &[Operator] = &[
+    Operator::prefix(Bang, Precedence::Unary, OperatorKind::Unary),
+    Operator::prefix(Minus, Precedence::Unary, OperatorKind::Unary),
+    Operator::prefix(Tilde, Precedence::Unary, OperatorKind::Unary),
+    // `..end` and `..=end`
+    Operator::prefix(DotDot, Precedence::Range, OperatorKind::Range),
+    Operator::prefix(DotDotEqual, Precedence::Range, OperatorKind::Range),
+    Operator::infix(Equal, Precedence::Assignment, Associativity::Right, OperatorKind::Assign),
+    Operator::infix(PlusEqual, Precedence::Assignment, Associativity::Right, OperatorKind::CompoundAssign),
+    Operator::infix(MinusEqual, Precedence::Assignment, Associativity::Right, OperatorKind::CompoundAssign),
+    Operator::infix(StarEqual, Precedence::Assignment, Associativity::Right, OperatorKind::CompoundAssign),
+    Operator::infix(SlashEqual, Precedence::Assignment, Associativity::Right, OperatorKind::CompoundAssign),
+    Operator::infix(PercentEqual, Precedence::Assignment, Associativity::Right, OperatorKind::CompoundAssign),
+    Operator::infix(DotDot, Precedence::Range, Associativity::None, OperatorKind::Range),
+    Operator::infix(DotDotEqual, Precedence::Range, Associativity::None, OperatorKind::Range),
+    Operator::infix(Or, Precedence::Or, Associativity::Left, OperatorKind::Logical),
+    Operator::infix(And, Precedence::And, Associativity::Left, OperatorKind::Logical),
+    Operator::infix(EqualEqual, Precedence::Equality, Associativity::Left, OperatorKind::Binary),
+    Operator::infix(BangEqual, Precedence::Equality, Associativity::Left, OperatorKind::Binary),
+    Operator::infix(Less, Precedence::Comparison, Associativity::Left, OperatorKind::Binary),
+    Operator::infix(LessEqual, Precedence::Comparison, Associativity::Left, OperatorKind::Binary),
+    Operator::infix(Greater, Precedence::Comparison, Associativity::Left, OperatorKind::Binary),
+    Operator::infix(GreaterEqual, Precedence::Comparison, Associativity::Left, OperatorKind::Binary),
+    Operator::infix(Pipe, Precedence::BitOr, Associativity::Left, OperatorKind::Binary),
+    Operator::infix(Caret, Precedence::BitXor, Associativity::Left, OperatorKind::Binary),
+    Operator::infix(Amp, Precedence::BitAnd, Associativity::Left, OperatorKind::Binary),
+    Operator::infix(LessLess, Precedence::Shift, Associativity::Left, OperatorKind::Binary),
+    Operator::infix(GreaterGreater, Precedence::Shift, Associativity::Left, OperatorKind::Binary),
+    Operator::infix(Plus, Precedence::Term, Associativity::Left, OperatorKind::Binary),
+    Operator::infix(Minus, Precedence::Term, Associativity::Left, OperatorKind::Binary),
+    Operator::infix(Star, Precedence::Factor, Associativity::Left, OperatorKind::Binary),
+    Operator::infix(Slash, Precedence::Factor, Associativity::Left, OperatorKind::Binary),
+    Operator::infix(Percent, Precedence::Factor, Associativity::Left, OperatorKind::Binary),
+    // the right operand of `as` is a type
+    Operator::infix(As, Precedence::Cast, Associativity::Left, OperatorKind::Cast),
+    Operator::postfix(LeftParen, Precedence::Postfix, OperatorKind::Call),
+    Operator::postfix(Question, Precedence::Postfix, OperatorKind::Try),
+    Operator::postfix(Dot, Precedence::Postfix, OperatorKind::Get),
+    Operator::postfix(LeftBracket, Precedence::Postfix, OperatorKind::Index),
+];
+
+/// How tightly operators bind, from loosest to tightest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Precedence {
+    Assignment,
+    Range,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
+    Term,
+    Factor,
+    Cast,
+    Unary,
+    Postfix,
+}
+
+/// How a chain of infix operators of the same precedence groups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Associativity {
+    /// `a - b - c` is `(a - b) - c`.
+    Left,
+    /// `a = b = c` is `a = (b = c)`.
+    Right,
+    /// `a..b..c` is an error.
+    None,
+}
+
+/// Where an operator goes relative to its operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fixity {
+    Prefix,
+    Infix(Associativity),
+    Postfix,
+}
+
+/// Which expression an operator builds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum OperatorKind {
+    Assign,
+    CompoundAssign,
+    Binary,
+    Logical,
+    Range,
+    Cast,
+    Unary,
+    Try,
+    Call,
+    Get,
+    Index,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operator {
+    pub token: TokenType,
+    pub fixity: Fixity,
+    pub precedence: Precedence,
+    pub kind: OperatorKind,
+}
+
+impl Operator {
+    const fn prefix(token: TokenType, precedence: Precedence, kind: OperatorKind) -> Self {
+        Self {
+            token,
+            fixity: Fixity::Prefix,
+            precedence,
+            kind,
+        }
+    }
+
+    const fn infix(
+        token: TokenType,
+        precedence: Precedence,
+        associativity: Associativity,
+        kind: OperatorKind,
+    ) -> Self {
+        Self {
+            token,
+            fixity: Fixity::Infix(associativity),
+            precedence,
+            kind,
+        }
+    }
+
+    const fn postfix(token: TokenType, precedence: Precedence, kind: OperatorKind) -> Self {
+        Self {
+            token,
+            fixity: Fixity::Postfix,
+            precedence,
+            kind,
+        }
+    }
+
+    /// Finds the prefix operator for a token.
+    #[must_use]
+    pub fn prefix_of(token: &TokenType) -> Option<&'static Self> {
+        OPERATORS
+            .iter()
+            .find(|op| op.fixity == Fixity::Prefix && op.token == *token)
+    }
+
+    /// Finds the infix or postfix operator for a token following an operand.
+    #[must_use]
+    pub fn infix_of(token: &TokenType) -> Option<&'static Self> {
+        OPERATORS
+            .iter()
+            .find(|op| op.fixity != Fixity::Prefix && op.token == *token)
+    }
+
+    /// How strongly the operator holds on to the operand to its left.
+    #[must_use]
+    pub const fn left_binding_power(&self) -> u8 {
+        let power = self.precedence as u8 * 2;
+        match self.fixity {
+            Fixity::Infix(Associativity::Right) => power + 1,
+            _ => power,
+        }
+    }
+
+    /// How strongly the operator holds on to the operand to its right.
+    #[must_use]
+    pub const fn right_binding_power(&self) -> u8 {
+        let power = self.precedence as u8 * 2;
+        match self.fixity {
+            Fixity::Infix(Associativity::Right) => power,
+            _ => power + 1,
+        }
+    }
+}
@@ -1,285 +1,1302 @@
+use super::lexer::Lexer;
+use super::operators::{Associativity, Fixity, Operator, OperatorKind};
 use super::TokenType::{
-    Bang, BangEqual, Else, Eof, Equal, EqualEqual, False, Fn, For, Greater, GreaterEqual,
-    Identifier, If, LeftBrace, LeftParen, Less, LessEqual, Let, Minus, Number, Or, Plus, Return,
-    RightBrace, RightParen, Semicolon, Slash, Star, String, True, While,
+    Arrow, Break, Char, Colon, ColonColon, Comma, Continue, DocComment, DotDotEqual, Else, Enum,
+    Eof, Equal, Error, False, FatArrow, Fn, For, Greater, GreaterEqual, GreaterGreater, Identifier,
+    If, In, InterpolationEnd, InterpolationMiddle, InterpolationStart, Label, LeftBrace,
+    LeftBracket, LeftParen, Less, Let, Loop, Match, Minus, MultilineString, Number, Or, Pipe,
+    Question, RawString, Return, RightBrace, RightBracket, RightParen, Semicolon, String, Struct,
+    True, While,
 };
-use super::{Expr, Stmt};
-use super::{Literal, Token, TokenType};
-use crate::error;
+use super::{
+    Arm, Expr, Field, ForClauses, Param, Part, Pattern, Stmt, TypeExpr, Variant, VariantFields,
+};
+use super::{Literal, Span, Token, TokenType};
 use anyhow::{anyhow, Result};
+use std::collections::VecDeque;
+
+/// The most parameters a function can have, and so the most arguments of a call.
+const MAX_ARITY: usize = 255;
 
-pub struct Parser {
-    tokens: Vec<Token>,
-    current: usize,
+/// Builds the AST, pulling tokens from a [`Lexer`] (or any other token source) as it goes.
+pub struct Parser<'src, I = Lexer<'src>> {
+    tokens: I,
+    /// The tokens after `previous`, the first one is what [`Parser::peek`] returns.
+    lookahead: VecDeque<Token<'src>>,
+    previous: Option<Token<'src>>,
+    module_docs: Vec<Token<'src>>,
+    /// Set while parsing an expression followed by a block, like the scrutinee of a
+    /// `match`, where `name {` starts that block rather than a struct literal.
+    no_struct_literals: bool,
 }
 
-impl Parser {
+impl<'src, I: Iterator<Item = Token<'src>>> Parser<'src, I> {
+    #[must_use]
+    pub fn new(tokens: impl IntoIterator<Item = Token<'src>, IntoIter = I>) -> Self {
+        let mut parser = Self {
+            tokens: tokens.into_iter(),
+            lookahead: VecDeque::new(),
+            previous: None,
+            module_docs: Vec::new(),
+            no_struct_literals: false,
+        };
+        parser.fill(1);
+        parser
+    }
+
+    /// The `//!` doc comments at the start of the file, available after [`Parser::parse`].
     #[inline]
     #[must_use]
-    pub const fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+    pub fn module_docs(&self) -> &[Token<'src>] {
+        &self.module_docs
     }
 
-    pub fn parse(&mut self) -> Vec<Stmt> {
+    pub fn parse(&mut self) -> Vec<Stmt<'src>> {
+        while self.check(&DocComment) && is_inner_doc(self.peek()) {
+            let doc = self.advance();
+            self.module_docs.push(doc);
+        }
+
         let mut statements = Vec::new();
-        loop {
+        while !self.is_at_end() {
             if let Some(stmt) = self.declaration() {
                 statements.push(stmt);
             }
-            if self.is_at_end() {
-                break;
-            }
         }
         statements
     }
 
-    fn declaration(&mut self) -> Option<Stmt> {
-        if self.matches(&[Let]) {
-            self.var_declaration().map_or_else(
-                |_| {
-                    self.synchronize();
-                    None
-                },
-                Some,
-            )
-        } else if let Ok(stmt) = self.statement() {
-            Some(stmt)
+    fn declaration(&mut self) -> Option<Stmt<'src>> {
+        let docs = self.doc_comments();
+        let stmt = if self.matches(&[Let]) {
+            self.var_declaration(docs)
+        } else if self.check(&Fn) && self.check_next(&Identifier) {
+            self.advance();
+            self.fn_statement(docs)
+        } else if self.matches(&[Struct]) {
+            self.struct_declaration(docs)
+        } else if self.matches(&[Enum]) {
+            self.enum_declaration(docs)
         } else {
-            self.synchronize();
-            None
+            if let Some(doc) = docs.first() {
+                crate::error_at_token(doc, "doc comments must be followed by a declaration");
+                // the `}` belongs to the enclosing block
+                if self.check(&RightBrace) || self.is_at_end() {
+                    return None;
+                }
+            }
+            self.statement()
+        };
+
+        stmt.map_or_else(
+            |_| {
+                self.synchronize();
+                None
+            },
+            Some,
+        )
+    }
+
+    /// Collects the `///` doc comments preceding a declaration.
+    fn doc_comments(&mut self) -> Vec<Token<'src>> {
+        let mut docs = Vec::new();
+        while self.matches(&[DocComment]) {
+            let doc = self.previous();
+            if is_inner_doc(&doc) {
+                crate::error_at_token(
+                    &doc,
+                    "inner doc comments are only allowed at the start of the file",
+                );
+            } else {
+                docs.push(doc);
+            }
         }
+        docs
     }
 
-    fn var_declaration(&mut self) -> Result<Stmt> {
-        let name = self.consume(&Identifier, "expected variable name.")?;
+    fn var_declaration(&mut self, docs: Vec<Token<'src>>) -> Result<Stmt<'src>> {
+        let keyword = self.previous();
+        let pattern = self.pattern()?;
+        let ty = if self.matches(&[Colon]) {
+            Some(self.type_expr()?)
+        } else {
+            None
+        };
         let initializer = if self.matches(&[Equal]) {
             self.expression().ok()
         } else {
             None
         };
-        self.consume(&Semicolon, "expected ';' after variable declaration.")?;
-        Ok(Stmt::Let(name, initializer))
+        let semicolon = self.consume(&Semicolon, "expected ';' after variable declaration.")?;
+        let span = keyword.span.to(semicolon.span);
+        Ok(Stmt::Let {
+            keyword,
+            pattern,
+            ty,
+            initializer,
+            docs,
+            span,
+        })
     }
 
-    fn fn_statement(&mut self) -> Result<Stmt> {
+    fn fn_statement(&mut self, docs: Vec<Token<'src>>) -> Result<Stmt<'src>> {
+        let keyword = self.previous();
         let name = self.consume(&Identifier, "expected function name")?;
-        // todo read function params
-        self.consume(&LeftParen, "expected '(' after function identifier")?;
-        self.consume(&RightParen, "function parameter list never closed")?;
-        let block = self.block();
+        self.consume(&LeftParen, "expected '(' after function name")?;
+        let params = self.parameters(&RightParen)?;
+        let return_type = self.return_type()?;
+        self.consume(&LeftBrace, "expected '{' before function body")?;
+        let body = self.block()?;
+        let span = keyword.span.to(body.span());
 
-        self.advance();
+        Ok(Stmt::Fn {
+            name,
+            params,
+            return_type,
+            body: Box::new(body),
+            docs,
+            span,
+        })
+    }
 
-        Ok(Stmt::Fn(name, vec![], Box::new(block[0].clone())))
+    fn struct_declaration(&mut self, docs: Vec<Token<'src>>) -> Result<Stmt<'src>> {
+        let keyword = self.previous();
+        let name = self.consume(&Identifier, "expected struct name")?;
+        self.consume(&LeftBrace, "expected '{' after struct name")?;
+        let fields = self.fields()?;
+        Ok(Stmt::Struct {
+            name,
+            fields,
+            docs,
+            span: keyword.span.to(self.previous().span),
+        })
     }
 
-    fn statement(&mut self) -> Result<Stmt> {
-        if self.matches(&[For]) {
-            self.for_statement()
-        } else if self.matches(&[If]) {
-            self.if_statement()
+    /// Parses the `name: Type` fields of a struct or enum variant after the `{`, up to
+    /// and including the `}`.
+    fn fields(&mut self) -> Result<Vec<Field<'src>>> {
+        let mut fields: Vec<Field<'src>> = Vec::new();
+        while !self.check(&RightBrace) {
+            let field = self.consume(&Identifier, "expected field name")?;
+            self.consume(&Colon, "expected ':' after field name")?;
+            let ty = self.type_expr()?;
+            if fields.iter().any(|other| other.name.name() == field.name()) {
+                crate::error_at_token(
+                    &field,
+                    format!("field `{}` is already declared", field.lexeme),
+                );
+            }
+            fields.push(Field {
+                span: field.span.to(ty.span()),
+                name: field,
+                ty,
+            });
+            if !self.matches(&[Comma]) {
+                break;
+            }
+        }
+        self.consume(&RightBrace, "expected '}' after fields")?;
+        Ok(fields)
+    }
+
+    fn enum_declaration(&mut self, docs: Vec<Token<'src>>) -> Result<Stmt<'src>> {
+        let keyword = self.previous();
+        let name = self.consume(&Identifier, "expected enum name")?;
+        self.consume(&LeftBrace, "expected '{' after enum name")?;
+
+        let mut variants: Vec<Variant<'src>> = Vec::new();
+        while !self.check(&RightBrace) {
+            let variant = self.consume(&Identifier, "expected variant name")?;
+            let fields = if self.matches(&[LeftParen]) {
+                VariantFields::Tuple(self.types()?.0)
+            } else if self.matches(&[LeftBrace]) {
+                VariantFields::Struct(self.fields()?)
+            } else {
+                VariantFields::Unit
+            };
+            if variants
+                .iter()
+                .any(|other| other.name.name() == variant.name())
+            {
+                crate::error_at_token(
+                    &variant,
+                    format!("variant `{}` is already declared", variant.lexeme),
+                );
+            }
+            let span = variant.span.to(self.previous().span);
+            variants.push(Variant {
+                name: variant,
+                fields,
+                span,
+            });
+            if !self.matches(&[Comma]) {
+                break;
+            }
+        }
+        let right_brace = self.consume(&RightBrace, "expected '}' after enum variants")?;
+
+        Ok(Stmt::Enum {
+            name,
+            variants,
+            docs,
+            span: keyword.span.to(right_brace.span),
+        })
+    }
+
+    /// Parses a parameter list after its `(` or `|`, up to and including the `close`
+    /// token. Default values are only allowed between parentheses, where they can't be
+    /// confused with the closing `|`.
+    fn parameters(&mut self, close: &TokenType) -> Result<Vec<Param<'src>>> {
+        let defaults = *close == RightParen;
+        let mut params: Vec<Param<'src>> = Vec::new();
+        while !self.check(close) {
+            if params.len() == MAX_ARITY {
+                error_at(
+                    self.peek(),
+                    &format!("functions can't have more than {MAX_ARITY} parameters"),
+                );
+            }
+            let param = self.parameter(defaults)?;
+            if param.default.is_none() && params.last().is_some_and(|last| last.default.is_some()) {
+                crate::error_at_token(
+                    &param.name,
+                    "parameters without a default value must come before those with one",
+                );
+            }
+            params.push(param);
+            if !self.matches(&[Comma]) {
+                break;
+            }
+        }
+        let message = if defaults {
+            "expected ')' after parameters"
+        } else {
+            "expected '|' after closure parameters"
+        };
+        self.consume(close, message)?;
+        Ok(params)
+    }
+
+    fn parameter(&mut self, defaults: bool) -> Result<Param<'src>> {
+        let name = self.consume(&Identifier, "expected parameter name")?;
+        let ty = if self.matches(&[Colon]) {
+            Some(self.type_expr()?)
+        } else {
+            None
+        };
+        let default = if defaults && self.matches(&[Equal]) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        let end = default
+            .as_ref()
+            .map(Expr::span)
+            .or_else(|| ty.as_ref().map(TypeExpr::span))
+            .unwrap_or(name.span);
+        Ok(Param {
+            span: name.span.to(end),
+            name,
+            ty,
+            default,
+        })
+    }
+
+    /// Parses the `-> Type` after the parameters of a function, if it has one.
+    fn return_type(&mut self) -> Result<Option<TypeExpr<'src>>> {
+        if self.matches(&[Arrow]) {
+            Ok(Some(self.type_expr()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn type_expr(&mut self) -> Result<TypeExpr<'src>> {
+        let mut ty = self.type_atom()?;
+        while self.matches(&[Question]) {
+            let span = ty.span().to(self.previous().span);
+            ty = TypeExpr::Optional(Box::new(ty), span);
+        }
+        Ok(ty)
+    }
+
+    /// Parses a type without the `?`s that make it optional.
+    fn type_atom(&mut self) -> Result<TypeExpr<'src>> {
+        if self.matches(&[LeftBracket]) {
+            let left_bracket = self.previous();
+            let element = self.type_expr()?;
+            let right_bracket =
+                self.consume(&RightBracket, "expected ']' after array element type")?;
+            let span = left_bracket.span.to(right_bracket.span);
+            return Ok(TypeExpr::Array(Box::new(element), span));
+        }
+        if self.matches(&[Fn]) {
+            let keyword = self.previous();
+            self.consume(&LeftParen, "expected '(' after 'fn'")?;
+            let (params, _) = self.types()?;
+            let return_type = self.return_type()?.map(Box::new);
+            let span = keyword.span.to(self.previous().span);
+            return Ok(TypeExpr::Fn(params, return_type, span));
+        }
+        if self.matches(&[LeftParen]) {
+            let left_paren = self.previous();
+            let (mut types, trailing_comma) = self.types()?;
+            let span = left_paren.span.to(self.previous().span);
+            // like expressions, `(T)` is only parenthesized and `(T,)` is a tuple
+            return Ok(match types.pop() {
+                Some(ty) if types.is_empty() && !trailing_comma => ty,
+                last => {
+                    types.extend(last);
+                    TypeExpr::Tuple(types, span)
+                }
+            });
+        }
+        let name = self.consume(&Identifier, "expected type")?;
+        let mut args = Vec::new();
+        if self.matches(&[Less]) {
+            while !matches!(self.peek().r#type, Greater | GreaterGreater | GreaterEqual) {
+                args.push(self.type_expr()?);
+                if !self.matches(&[Comma]) {
+                    break;
+                }
+            }
+            self.close_angle_bracket()?;
+        }
+        let span = name.span.to(self.previous().span);
+        Ok(TypeExpr::Named(name, args, span))
+    }
+
+    /// Consumes the `>` closing type arguments. The `>>` ending nested ones, like in
+    /// `Vec<Vec<T>>`, and the `>=` of `let v: Vec<T>= w` are single tokens, so only their
+    /// first half is consumed. (`>>=` is lexed as `>>` and `=`.)
+    fn close_angle_bracket(&mut self) -> Result<Token<'src>> {
+        let rest = match self.peek().r#type {
+            GreaterGreater => Greater,
+            GreaterEqual => Equal,
+            _ => return self.consume(&Greater, "expected '>' after type arguments"),
+        };
+        let token = &mut self.lookahead[0];
+        let (first, second) = token.lexeme.split_at(1);
+        let start = token.span.start;
+        let mut closing = Token::new(
+            Greater,
+            first,
+            None,
+            Span::new(start, start + 1),
+            token.line,
+            token.column,
+        );
+        closing.leading_trivia = std::mem::take(&mut token.leading_trivia);
+        token.r#type = rest;
+        token.lexeme = second;
+        token.span = Span::new(start + 1, token.span.end);
+        token.column += 1;
+        self.previous = Some(closing.clone());
+        Ok(closing)
+    }
+
+    /// Parses comma separated types after a `(`, up to and including the `)`, and whether
+    /// they end with a comma.
+    fn types(&mut self) -> Result<(Vec<TypeExpr<'src>>, bool)> {
+        let mut types = Vec::new();
+        let mut trailing_comma = false;
+        while !self.check(&RightParen) {
+            types.push(self.type_expr()?);
+            trailing_comma = self.matches(&[Comma]);
+            if !trailing_comma {
+                break;
+            }
+        }
+        self.consume(&RightParen, "expected ')' after types")?;
+        Ok((types, trailing_comma))
+    }
+
+    fn statement(&mut self) -> Result<Stmt<'src>> {
+        if self.at_labeled_statement() {
+            let label = self.advance();
+            self.advance();
+            if self.matches(&[For]) {
+                self.for_statement(Some(label))
+            } else {
+                self.advance();
+                self.while_statement(Some(label))
+            }
+        } else if self.matches(&[For]) {
+            self.for_statement(None)
+        } else if self.matches(&[Return]) {
+            self.return_statement()
+        } else if self.matches(&[Break]) {
+            self.break_statement()
+        } else if self.matches(&[Continue]) {
+            self.continue_statement()
         } else if self.matches(&[While]) {
-            self.while_statement()
-        } else if self.matches(&[LeftBrace]) {
-            Ok(Stmt::Block(self.block()))
-        } else if self.matches(&[Fn]) {
-            self.fn_statement()
+            self.while_statement(None)
         } else {
             self.expression_statement()
         }
     }
 
-    fn for_statement(&mut self) -> Result<Stmt> {
-        self.consume(&LeftParen, "Expect '(' after 'for'.")?;
+    /// Whether the next statement is a labeled `for` or `while` loop. Labeled `loop`s are
+    /// expressions.
+    fn at_labeled_statement(&mut self) -> bool {
+        self.check(&Label)
+            && self.check_next(&Colon)
+            && matches!(self.peek_nth(2).r#type, For | While)
+    }
+
+    /// Parses a `for` loop after its keyword, either C-style or over an iterable.
+    fn for_statement(&mut self, label: Option<Token<'src>>) -> Result<Stmt<'src>> {
+        let keyword = self.previous();
+        let (clauses, body) = if self.at_c_style_for() {
+            let clauses = self.c_style_clauses()?;
+            (clauses, self.statement()?)
+        } else {
+            let pattern = self.pattern()?;
+            self.consume(&In, "expected 'in' after for loop pattern")?;
+            let iterable = self.with_struct_literals(false, Self::expression)?;
+            self.consume(&LeftBrace, "expected '{' before loop body")?;
+            let body = self.block()?;
+            let span = body.span();
+            (
+                ForClauses::In { pattern, iterable },
+                Stmt::Expression(body, span),
+            )
+        };
+        let span = label.as_ref().unwrap_or(&keyword).span.to(body.span());
+        Ok(Stmt::For {
+            label,
+            keyword,
+            clauses: Box::new(clauses),
+            body: Box::new(body),
+            span,
+        })
+    }
+
+    /// Whether a `for` loop has C-style clauses in parentheses, rather than a pattern
+    /// that starts with one, like `for (a, b) in pairs`, which has an `in` after the `)`.
+    ///
+    /// Patterns can't contain a `;` and clauses can't contain an `in`, so the lookahead
+    /// stops at the first of either, and never reaches past the loop's header, even when
+    /// its parentheses aren't balanced.
+    fn at_c_style_for(&mut self) -> bool {
+        if !self.check(&LeftParen) {
+            return false;
+        }
+        let mut depth = 0;
+        for n in 0.. {
+            match self.peek_nth(n).r#type {
+                LeftParen => depth += 1,
+                RightParen if depth == 1 => return self.peek_nth(n + 1).r#type != In,
+                RightParen => depth -= 1,
+                Semicolon | Eof => break,
+                In => return false,
+                _ => {}
+            }
+        }
+        true
+    }
+
+    /// Parses `(initializer; condition; increment)` after `for`.
+    fn c_style_clauses(&mut self) -> Result<ForClauses<'src>> {
+        self.consume(&LeftParen, "expected '(' after 'for'")?;
         let initializer = if self.matches(&[Semicolon]) {
             None
         } else if self.matches(&[Let]) {
-            self.var_declaration().ok()
+            self.var_declaration(Vec::new()).ok()
         } else {
             self.expression_statement().ok()
-        };
+        }
+        .map(Box::new);
         let condition = if self.check(&Semicolon) {
-            Expr::Literal(Literal::Bool(true))
+            None
         } else {
-            self.expression()?
+            Some(self.expression()?)
         };
-
+        self.consume(&Semicolon, "expected ';' after loop condition")?;
         let increment = if self.check(&RightParen) {
             None
         } else {
             self.expression().ok()
         };
         self.consume(&RightParen, "expected ')' after for clauses")?;
-        let mut body = self.statement()?;
-        if let Some(increment) = increment {
-            body = Stmt::Block(vec![body, Stmt::Expression(increment)]);
-        }
-        body = Stmt::While(condition, Box::new(body));
-        if let Some(initializer) = initializer {
-            body = Stmt::Block(vec![initializer, body]);
-        }
-        Ok(body)
+        Ok(ForClauses::CStyle {
+            initializer,
+            condition,
+            increment,
+        })
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt<'src>> {
+        let keyword = self.previous();
+        let value = if self.check(&Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        let semicolon = self.consume(&Semicolon, "expected ';' after return value")?;
+        let span = keyword.span.to(semicolon.span);
+        Ok(Stmt::Return(keyword, value, span))
     }
 
-    fn while_statement(&mut self) -> Result<Stmt> {
+    fn break_statement(&mut self) -> Result<Stmt<'src>> {
+        let keyword = self.previous();
+        let label = self.matches(&[Label]).then(|| self.previous());
+        let value = if self.check(&Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        let semicolon = self.consume(&Semicolon, "expected ';' after break")?;
+        let span = keyword.span.to(semicolon.span);
+        Ok(Stmt::Break(keyword, label, value, span))
+    }
+
+    fn continue_statement(&mut self) -> Result<Stmt<'src>> {
+        let keyword = self.previous();
+        let label = self.matches(&[Label]).then(|| self.previous());
+        let semicolon = self.consume(&Semicolon, "expected ';' after continue")?;
+        let span = keyword.span.to(semicolon.span);
+        Ok(Stmt::Continue(keyword, label, span))
+    }
+
+    fn while_statement(&mut self, label: Option<Token<'src>>) -> Result<Stmt<'src>> {
+        let keyword = self.previous();
         self.consume(&LeftParen, "expected '(' after 'while'")?;
         let condition = self.expression()?;
         self.consume(&RightParen, "expected ')' after the condition")?;
         let body = self.statement()?;
-        Ok(Stmt::While(condition, Box::new(body)))
+        let span = label.as_ref().unwrap_or(&keyword).span.to(body.span());
+        Ok(Stmt::While {
+            label,
+            condition,
+            body: Box::new(body),
+            increment: None,
+            span,
+        })
     }
 
-    fn if_statement(&mut self) -> Result<Stmt> {
-        self.consume(&LeftParen, "expected '(' after 'if'")?;
-        let condition = self.expression()?;
-        self.consume(&RightParen, "expected ')' after if condition")?;
-        let then_branch = self.statement()?;
-        let else_branch = if self.matches(&[Else]) {
-            self.statement().ok()
-        } else {
+    /// Parses an `if` after its keyword. A condition in parentheses can be followed by any
+    /// statement, and so can its `else`, otherwise the branches have to be blocks.
+    fn if_expr(&mut self) -> Result<Expr<'src>> {
+        let keyword = self.previous();
+        let (condition, then_branch, statement_branches) = match self.if_condition()? {
+            condition @ Expr::Grouping(..) if !self.check(&LeftBrace) => {
+                (condition, self.statement_block()?, true)
+            }
+            condition => {
+                self.consume(&LeftBrace, "expected '{' after if condition")?;
+                (condition, self.block()?, false)
+            }
+        };
+        let else_branch = if !self.matches(&[Else]) {
             None
+        } else if self.matches(&[If]) {
+            Some(self.if_expr()?)
+        } else if statement_branches && !self.check(&LeftBrace) {
+            Some(self.statement_block()?)
+        } else {
+            self.consume(&LeftBrace, "expected '{' or 'if' after 'else'")?;
+            Some(self.block()?)
         };
-        Ok(Stmt::If(
-            condition,
-            Box::new(then_branch),
-            Box::new(else_branch),
-        ))
+        let end = else_branch.as_ref().unwrap_or(&then_branch).span();
+        Ok(Expr::If {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch: else_branch.map(Box::new),
+            span: keyword.span.to(end),
+        })
     }
 
-    fn block(&mut self) -> Vec<Stmt> {
-        let mut statements = Vec::new();
-        loop {
-            if let Some(stmt) = self.declaration() {
-                statements.push(stmt);
+    /// Parses the condition of an `if`, where a `{` starts the branch rather than a struct
+    /// literal. A condition that starts with a parenthesized expression goes on past the
+    /// `)` as long as an operator follows, so `if (a || b) && c {}` works, and only ends
+    /// there when a statement does, as in `if (done) return;`.
+    fn if_condition(&mut self) -> Result<Expr<'src>> {
+        self.with_struct_literals(false, |parser| {
+            if !parser.matches(&[LeftParen]) {
+                return parser.expression();
             }
-            if self.check(&RightBrace) || self.is_at_end() {
-                break;
-            }
-        }
-        //self.consume(&RightBrace, "Expect ';' after block.")?;
-        statements
+            let grouping = parser.with_struct_literals(true, Self::grouping_or_tuple)?;
+            parser.operators(grouping, None, 0)
+        })
     }
 
-    fn expression_statement(&mut self) -> Result<Stmt> {
-        let expr = self.expression()?;
-        self.consume(&Semicolon, "expected ';' after expression")?;
-        Ok(Stmt::Expression(expr))
+    /// Parses a statement that's the branch of an `if`, as a block of its own.
+    fn statement_block(&mut self) -> Result<Expr<'src>> {
+        let stmt = self.statement()?;
+        let span = stmt.span();
+        Ok(block(vec![stmt], span))
     }
 
-    fn expression(&mut self) -> Result<Expr> {
-        self.assignment()
+    /// Parses a block after its `{`, up to and including the `}`, whose value is the
+    /// expression it ends with, if that isn't followed by a semicolon.
+    fn block(&mut self) -> Result<Expr<'src>> {
+        self.with_struct_literals(true, Self::finish_block)
     }
 
-    fn assignment(&mut self) -> Result<Expr> {
-        let expr = self.or()?;
-        if self.matches(&[Equal]) {
-            let equals = self.previous();
-            let value = self.assignment()?;
-            if let Expr::Variable(name) = expr {
-                Ok(Expr::Assign(name, Box::new(value)))
-            } else {
-                error(equals.line, "invalid assignment target");
-                Ok(expr)
+    fn finish_block(&mut self) -> Result<Expr<'src>> {
+        let left_brace = self.previous();
+        let mut statements = Vec::new();
+        while !self.check(&RightBrace) && !self.is_at_end() {
+            if let Some(stmt) = self.declaration() {
+                statements.push(stmt);
             }
-        } else {
-            Ok(expr)
         }
+        let right_brace = self.consume(&RightBrace, "expected '}' after block")?;
+        Ok(block(statements, left_brace.span.to(right_brace.span)))
     }
 
-    fn or(&mut self) -> Result<Expr> {
-        let mut expr = self.and()?;
-        while self.matches(&[Or]) {
-            let operator = self.previous();
-            let right = self.and()?;
-            expr = Expr::Logical(Box::new(expr), operator, Box::new(right));
+    fn expression_statement(&mut self) -> Result<Stmt<'src>> {
+        let expr = self.statement_expression()?;
+        // the last expression of a block doesn't need one either, it's the block's value
+        if (is_block_like(&expr) && !self.check(&Semicolon)) || self.check(&RightBrace) {
+            let span = expr.span();
+            return Ok(Stmt::Expression(expr, span));
         }
-        Ok(expr)
+        let semicolon = self.consume(&Semicolon, "expected ';' after expression")?;
+        let span = expr.span().to(semicolon.span);
+        Ok(Stmt::Expression(expr, span))
     }
 
-    fn and(&mut self) -> Result<Expr> {
-        let mut expr = self.equality()?;
-        while self.matches(&[Or]) {
-            let operator = self.previous();
-            let right = self.equality()?;
-            expr = Expr::Logical(Box::new(expr), operator, Box::new(right));
+    /// Parses the expression of an expression statement. One that ends with a block, like
+    /// a `match`, ends the statement there, so it doesn't need a semicolon.
+    fn statement_expression(&mut self) -> Result<Expr<'src>> {
+        if matches!(self.peek().r#type, If | LeftBrace | Match | Loop | Label) {
+            self.primary()
+        } else {
+            self.expression()
         }
-        Ok(expr)
     }
 
-    fn equality(&mut self) -> Result<Expr> {
-        let mut expr = self.comparison()?;
-        while self.matches(&[BangEqual, EqualEqual]) {
-            let operator = self.previous();
-            let right = self.comparison()?;
-            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+    fn expression(&mut self) -> Result<Expr<'src>> {
+        self.expression_bp(0)
+    }
+
+    /// Parses with struct literals allowed or not, restoring the outer setting after.
+    fn with_struct_literals<T>(
+        &mut self,
+        allowed: bool,
+        parse: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        let outer = std::mem::replace(&mut self.no_struct_literals, !allowed);
+        let result = parse(self);
+        self.no_struct_literals = outer;
+        result
+    }
+
+    /// Parses an expression whose operators all bind at least as strongly as
+    /// `min_binding_power`, following the [`OPERATORS`](super::operators::OPERATORS) table.
+    fn expression_bp(&mut self, min_binding_power: u8) -> Result<Expr<'src>> {
+        let last = Operator::prefix_of(&self.peek().r#type);
+        let expr = match last {
+            Some(op) => {
+                let token = self.advance();
+                self.prefix(op, token)?
+            }
+            None => self.primary()?,
+        };
+        self.operators(expr, last, min_binding_power)
+    }
+
+    /// Parses the infix and postfix operators that follow `expr`, which ends with `last`,
+    /// as long as they bind at least as strongly as `min_binding_power`.
+    fn operators(
+        &mut self,
+        mut expr: Expr<'src>,
+        mut last: Option<&Operator>,
+        min_binding_power: u8,
+    ) -> Result<Expr<'src>> {
+        while let Some(op) = Operator::infix_of(&self.peek().r#type) {
+            if op.left_binding_power() < min_binding_power {
+                break;
+            }
+            let token = self.advance();
+            if op.fixity == Fixity::Infix(Associativity::None)
+                && last.is_some_and(|last| last.precedence == op.precedence)
+            {
+                crate::error_at_token(
+                    &token,
+                    format!("`{}` can't be chained, add parentheses", token.lexeme),
+                );
+            }
+            expr = if op.fixity == Fixity::Postfix {
+                self.postfix(expr, op, token)?
+            } else {
+                self.infix(expr, op, token)?
+            };
+            last = Some(op);
         }
         Ok(expr)
     }
 
-    fn comparison(&mut self) -> Result<Expr> {
-        let mut expr = self.term()?;
-        while self.matches(&[Greater, GreaterEqual, Less, LessEqual]) {
-            let operator = self.previous();
-            let right = self.term()?;
-            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+    fn prefix(&mut self, op: &Operator, operator: Token<'src>) -> Result<Expr<'src>> {
+        match op.kind {
+            OperatorKind::Range => self.range(None, operator, op),
+            _ => {
+                let right = self.expression_bp(op.right_binding_power())?;
+                let span = operator.span.to(right.span());
+                Ok(Expr::Unary(operator, Box::new(right), span))
+            }
         }
-        Ok(expr)
     }
 
-    fn term(&mut self) -> Result<Expr> {
-        let mut expr = self.factor()?;
-        while self.matches(&[Plus, Minus]) {
-            let operator = self.previous();
-            let right = self.factor()?;
-            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+    fn infix(
+        &mut self,
+        left: Expr<'src>,
+        op: &Operator,
+        operator: Token<'src>,
+    ) -> Result<Expr<'src>> {
+        match op.kind {
+            OperatorKind::Range => return self.range(Some(left), operator, op),
+            OperatorKind::Cast => {
+                // a `?` after the type is the try operator, so casting to an optional
+                // type takes parentheses, `x as (T?)`
+                let ty = self.type_atom()?;
+                let span = left.span().to(ty.span());
+                return Ok(Expr::Cast(Box::new(left), ty, span));
+            }
+            _ => {}
         }
-        Ok(expr)
+
+        let right = self.expression_bp(op.right_binding_power())?;
+        let span = left.span().to(right.span());
+        let (left, right) = (Box::new(left), Box::new(right));
+        Ok(match op.kind {
+            OperatorKind::Assign => match *left {
+                Expr::Variable(name, _) => Expr::Assign(name, right, span),
+                Expr::Get(object, name, _) => Expr::Set(object, name, right, span),
+                Expr::Index(object, index, _) => Expr::IndexSet(object, index, right, span),
+                left => {
+                    crate::error_at_token(&operator, "invalid assignment target");
+                    left
+                }
+            },
+            OperatorKind::CompoundAssign => match *left {
+                Expr::Variable(name, _) => Expr::CompoundAssign(name, operator, right, span),
+                Expr::Get(object, name, _) => {
+                    Expr::CompoundSet(object, name, operator, right, span)
+                }
+                Expr::Index(object, index, _) => {
+                    Expr::CompoundIndexSet(object, index, operator, right, span)
+                }
+                left => {
+                    crate::error_at_token(&operator, "invalid assignment target");
+                    left
+                }
+            },
+            OperatorKind::Logical => Expr::Logical(left, operator, right, span),
+            _ => Expr::Binary(left, operator, right, span),
+        })
     }
 
-    fn factor(&mut self) -> Result<Expr> {
-        let mut expr = self.unary()?;
-        while self.matches(&[Slash, Star]) {
-            let operator = self.previous();
-            let right = self.unary()?;
-            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+    fn postfix(
+        &mut self,
+        left: Expr<'src>,
+        op: &Operator,
+        operator: Token<'src>,
+    ) -> Result<Expr<'src>> {
+        match op.kind {
+            OperatorKind::Call => self.finish_call(left),
+            OperatorKind::Get => {
+                let name = self.consume(&Identifier, "expected field name after '.'")?;
+                let span = left.span().to(name.span);
+                Ok(Expr::Get(Box::new(left), name, span))
+            }
+            OperatorKind::Index => {
+                let index = self.with_struct_literals(true, Self::expression)?;
+                let right_bracket = self.consume(&RightBracket, "expected ']' after index")?;
+                let span = left.span().to(right_bracket.span);
+                Ok(Expr::Index(Box::new(left), Box::new(index), span))
+            }
+            _ => {
+                let span = left.span().to(operator.span);
+                Ok(Expr::Try(Box::new(left), span))
+            }
         }
-        Ok(expr)
     }
 
-    fn unary(&mut self) -> Result<Expr> {
-        if self.matches(&[Bang, Minus]) {
-            let operator = self.previous();
-            let right = self.unary()?;
-            Ok(Expr::Unary(operator, Box::new(right)))
+    /// Parses a range after its operator, whose end is optional unless it's `..=`.
+    fn range(
+        &mut self,
+        start: Option<Expr<'src>>,
+        operator: Token<'src>,
+        op: &Operator,
+    ) -> Result<Expr<'src>> {
+        let end = if self.can_begin_expression() {
+            Some(self.expression_bp(op.right_binding_power())?)
         } else {
-            self.primary()
-        }
+            if operator.r#type == DotDotEqual {
+                crate::error_at_token(&operator, "inclusive ranges must have an end");
+            }
+            None
+        };
+
+        let span = start
+            .as_ref()
+            .map_or(operator.span, Expr::span)
+            .to(end.as_ref().map_or(operator.span, Expr::span));
+        Ok(Expr::Range(
+            start.map(Box::new),
+            operator,
+            end.map(Box::new),
+            span,
+        ))
+    }
+
+    /// Parses the arguments of a call after its `(`.
+    fn finish_call(&mut self, callee: Expr<'src>) -> Result<Expr<'src>> {
+        let args = self.with_struct_literals(true, |parser| {
+            let mut args = Vec::new();
+            while !parser.check(&RightParen) {
+                if args.len() == MAX_ARITY {
+                    error_at(
+                        parser.peek(),
+                        &format!("calls can't have more than {MAX_ARITY} arguments"),
+                    );
+                }
+                args.push(parser.expression()?);
+                if !parser.matches(&[Comma]) {
+                    break;
+                }
+            }
+            Ok(args)
+        })?;
+        let paren = self.consume(&RightParen, "expected ')' after arguments")?;
+        let span = callee.span().to(paren.span);
+        Ok(Expr::Call {
+            callee: Box::new(callee),
+            args,
+            paren,
+            span,
+        })
     }
 
-    fn primary(&mut self) -> Result<Expr> {
+    fn primary(&mut self) -> Result<Expr<'src>> {
         if self.matches(&[False]) {
-            return Ok(Expr::Literal(Literal::Bool(false)));
+            return Ok(Expr::Literal(Literal::Bool(false), self.previous().span));
         }
         if self.matches(&[True]) {
-            return Ok(Expr::Literal(Literal::Bool(true)));
+            return Ok(Expr::Literal(Literal::Bool(true), self.previous().span));
         }
-        if self.matches(&[Number, String]) {
+        // malformed literals stand in for the expression they were meant to be
+        if self.matches(&[Number, String, RawString, MultilineString, Char, Error]) {
+            let token = self.previous();
             return Ok(Expr::Literal(
-                self.previous().literal.map_or(Literal::Nil, |lit| lit),
+                token.literal.map_or(Literal::Nil, |lit| lit),
+                token.span,
             ));
         }
+        if self.matches(&[InterpolationStart]) {
+            return self.interpolation();
+        }
         if self.matches(&[Identifier]) {
-            return Ok(Expr::Variable(self.previous()));
+            let name = self.previous();
+            if self.check(&ColonColon) {
+                return self.path(name);
+            }
+            if self.at_struct_literal() {
+                return self.struct_literal(name);
+            }
+            let span = name.span;
+            return Ok(Expr::Variable(name, span));
+        }
+        if self.matches(&[Fn, Pipe]) || (self.at_empty_closure() && self.matches(&[Or])) {
+            return self.lambda();
+        }
+        if self.matches(&[If]) {
+            return self.if_expr();
+        }
+        if self.matches(&[LeftBrace]) {
+            return self.block();
+        }
+        if self.matches(&[Match]) {
+            return self.match_expr();
+        }
+        if self.matches(&[Loop]) {
+            return self.loop_expr(None);
+        }
+        if self.matches(&[Label]) {
+            let label = self.previous();
+            self.consume(&Colon, "expected ':' after label")?;
+            if self.check(&For) || self.check(&While) {
+                error_at(
+                    self.peek(),
+                    "labeled `for` and `while` loops can't be used as values",
+                );
+                return Err(anyhow!("Parse error"));
+            }
+            self.consume(&Loop, "expected a loop after label")?;
+            return self.loop_expr(Some(label));
+        }
+        if self.matches(&[LeftBracket]) {
+            return self.with_struct_literals(true, Self::array);
         }
         if self.matches(&[LeftParen]) {
-            let expr = self.expression()?;
-            self.consume(&RightParen, "expected `)` after expression")?;
-            return Ok(Expr::Grouping(Box::new(expr)));
+            return self.with_struct_literals(true, Self::grouping_or_tuple);
         }
-        crate::error_at_token(&self.peek(), "expected expression");
+        error_at(self.peek(), "expected expression");
         Err(anyhow!("Parse error"))
     }
 
+    /// Parses a parenthesized expression or a tuple after the `(`. A comma after the first
+    /// element makes a tuple.
+    fn grouping_or_tuple(&mut self) -> Result<Expr<'src>> {
+        let left_paren = self.previous();
+        if self.matches(&[RightParen]) {
+            let span = left_paren.span.to(self.previous().span);
+            return Ok(Expr::Tuple(Vec::new(), span));
+        }
+        let first = self.expression()?;
+        if !self.check(&Comma) {
+            let right_paren = self.consume(&RightParen, "expected `)` after expression")?;
+            let span = left_paren.span.to(right_paren.span);
+            return Ok(Expr::Grouping(Box::new(first), span));
+        }
+
+        let mut elements = vec![first];
+        while self.matches(&[Comma]) && !self.check(&RightParen) {
+            elements.push(self.expression()?);
+        }
+        let right_paren = self.consume(&RightParen, "expected `)` after tuple elements")?;
+        let span = left_paren.span.to(right_paren.span);
+        Ok(Expr::Tuple(elements, span))
+    }
+
+    /// Parses an array literal after its `[`, either listing the elements or repeating
+    /// one value, like `[0; 10]`.
+    fn array(&mut self) -> Result<Expr<'src>> {
+        let left_bracket = self.previous();
+        let mut elements = Vec::new();
+        while !self.check(&RightBracket) {
+            let element = self.expression()?;
+            if elements.is_empty() && self.matches(&[Semicolon]) {
+                let count = self.expression()?;
+                let right_bracket =
+                    self.consume(&RightBracket, "expected ']' after array length")?;
+                let span = left_bracket.span.to(right_bracket.span);
+                return Ok(Expr::ArrayRepeat(Box::new(element), Box::new(count), span));
+            }
+            elements.push(element);
+            if !self.matches(&[Comma]) {
+                break;
+            }
+        }
+        let right_bracket = self.consume(&RightBracket, "expected ']' after array elements")?;
+        Ok(Expr::Array(
+            elements,
+            left_bracket.span.to(right_bracket.span),
+        ))
+    }
+
+    /// Whether a `{` after a name starts a struct literal rather than a block, by looking
+    /// at what follows it: `}`, or a field name followed by `:`, `,` or `}`.
+    fn at_struct_literal(&mut self) -> bool {
+        if self.no_struct_literals || !self.check(&LeftBrace) {
+            return false;
+        }
+        match self.peek_nth(1).r#type {
+            RightBrace => true,
+            Identifier => matches!(self.peek_nth(2).r#type, Colon | Comma | RightBrace),
+            _ => false,
+        }
+    }
+
+    /// Parses `{ x: 1, y }` after the name of a struct.
+    fn struct_literal(&mut self, name: Token<'src>) -> Result<Expr<'src>> {
+        self.advance();
+        let mut fields: Vec<(Token<'src>, Expr<'src>)> = Vec::new();
+        while !self.check(&RightBrace) {
+            let field = self.consume(&Identifier, "expected field name")?;
+            let value = if self.matches(&[Colon]) {
+                self.expression()?
+            } else {
+                let span = field.span;
+                Expr::Variable(field.clone(), span)
+            };
+            if fields.iter().any(|(other, _)| other.name() == field.name()) {
+                crate::error_at_token(
+                    &field,
+                    format!("field `{}` is specified more than once", field.lexeme),
+                );
+            }
+            fields.push((field, value));
+            if !self.matches(&[Comma]) {
+                break;
+            }
+        }
+        let right_brace = self.consume(&RightBrace, "expected '}' after struct fields")?;
+        let span = name.span.to(right_brace.span);
+        Ok(Expr::StructLiteral(name, fields, span))
+    }
+
+    /// Parses a `loop` after its keyword.
+    fn loop_expr(&mut self, label: Option<Token<'src>>) -> Result<Expr<'src>> {
+        let keyword = self.previous();
+        self.consume(&LeftBrace, "expected '{' after 'loop'")?;
+        let body = self.block()?;
+        let span = label.as_ref().unwrap_or(&keyword).span.to(body.span());
+        Ok(Expr::Loop {
+            label,
+            body: Box::new(body),
+            span,
+        })
+    }
+
+    /// Parses a `match` expression after its keyword.
+    fn match_expr(&mut self) -> Result<Expr<'src>> {
+        let keyword = self.previous();
+        let scrutinee = self.with_struct_literals(false, Self::expression)?;
+        self.consume(&LeftBrace, "expected '{' after match scrutinee")?;
+
+        let mut arms = Vec::new();
+        while !self.check(&RightBrace) && !self.is_at_end() {
+            let pattern = self.pattern()?;
+            let guard = if self.matches(&[If]) {
+                Some(self.expression()?)
+            } else {
+                None
+            };
+            self.consume(&FatArrow, "expected '=>' after match pattern")?;
+            let body = if self.matches(&[LeftBrace]) {
+                self.block()?
+            } else {
+                self.expression()?
+            };
+            let block_like = is_block_like(&body);
+            arms.push(Arm {
+                span: pattern.span().to(body.span()),
+                pattern,
+                guard,
+                body,
+            });
+            // arms ending with a block don't need a comma
+            if !self.matches(&[Comma]) && !self.check(&RightBrace) && !block_like {
+                error_at(self.peek(), "expected ',' after match arm");
+                return Err(anyhow!("Parse error"));
+            }
+        }
+        let right_brace = self.consume(&RightBrace, "expected '}' after match arms")?;
+
+        let span = keyword.span.to(right_brace.span);
+        Ok(Expr::Match {
+            keyword,
+            scrutinee: Box::new(scrutinee),
+            arms,
+            span,
+        })
+    }
+
+    fn pattern(&mut self) -> Result<Pattern<'src>> {
+        if self.matches(&[
+            Number,
+            String,
+            RawString,
+            MultilineString,
+            Char,
+            Error,
+            True,
+            False,
+        ]) {
+            let token = self.previous();
+            let literal = match token.r#type {
+                True => Literal::Bool(true),
+                False => Literal::Bool(false),
+                _ => token.literal.unwrap_or(Literal::Nil),
+            };
+            return Ok(Pattern::Literal(literal, token.span));
+        }
+        if self.matches(&[Minus]) {
+            let minus = self.previous();
+            let number = self.consume(&Number, "expected a number after '-' in pattern")?;
+            let span = minus.span.to(number.span);
+            return Ok(Pattern::Negative(
+                number.literal.unwrap_or(Literal::Nil),
+                span,
+            ));
+        }
+        if self.matches(&[LeftParen]) {
+            let left_paren = self.previous();
+            let (mut patterns, trailing_comma) = self.patterns()?;
+            let span = left_paren.span.to(self.previous().span);
+            // `(pattern)` is only parenthesized, `(pattern,)` is a tuple
+            return Ok(match patterns.pop() {
+                Some(pattern) if patterns.is_empty() && !trailing_comma => pattern,
+                last => {
+                    patterns.extend(last);
+                    Pattern::Tuple(patterns, span)
+                }
+            });
+        }
+
+        let first = self.consume(&Identifier, "expected pattern")?;
+        let start = first.span;
+        let mut path = vec![first];
+        while self.matches(&[ColonColon]) {
+            path.push(self.consume(&Identifier, "expected identifier after `::`")?);
+        }
+        if self.matches(&[LeftParen]) {
+            let (patterns, _) = self.patterns()?;
+            let span = start.to(self.previous().span);
+            Ok(Pattern::Variant(path, patterns, span))
+        } else if self.matches(&[LeftBrace]) {
+            let fields = self.field_patterns()?;
+            let span = start.to(self.previous().span);
+            Ok(Pattern::Struct(path, fields, span))
+        } else if path.len() > 1 {
+            let span = start.to(self.previous().span);
+            Ok(Pattern::Variant(path, Vec::new(), span))
+        } else if path[0].lexeme == "_" {
+            Ok(Pattern::Wildcard(start))
+        } else {
+            Ok(Pattern::Binding(path.remove(0), start))
+        }
+    }
+
+    /// Parses comma separated patterns after a `(`, up to and including the `)`, and
+    /// whether they end with a comma.
+    fn patterns(&mut self) -> Result<(Vec<Pattern<'src>>, bool)> {
+        let mut patterns = Vec::new();
+        let mut trailing_comma = false;
+        while !self.check(&RightParen) {
+            patterns.push(self.pattern()?);
+            trailing_comma = self.matches(&[Comma]);
+            if !trailing_comma {
+                break;
+            }
+        }
+        self.consume(&RightParen, "expected ')' after patterns")?;
+        Ok((patterns, trailing_comma))
+    }
+
+    /// Parses the `x` and `y: pattern` fields of a struct pattern after the `{`, up to
+    /// and including the `}`.
+    fn field_patterns(&mut self) -> Result<Vec<(Token<'src>, Pattern<'src>)>> {
+        let mut fields: Vec<(Token<'src>, Pattern<'src>)> = Vec::new();
+        while !self.check(&RightBrace) {
+            let field = self.consume(&Identifier, "expected field name")?;
+            let pattern = if self.matches(&[Colon]) {
+                self.pattern()?
+            } else {
+                let span = field.span;
+                Pattern::Binding(field.clone(), span)
+            };
+            if fields.iter().any(|(other, _)| other.name() == field.name()) {
+                crate::error_at_token(
+                    &field,
+                    format!("field `{}` is specified more than once", field.lexeme),
+                );
+            }
+            fields.push((field, pattern));
+            if !self.matches(&[Comma]) {
+                break;
+            }
+        }
+        self.consume(&RightBrace, "expected '}' after field patterns")?;
+        Ok(fields)
+    }
+
+    /// Parses an anonymous function, `fn(a, b) { a + b }` or `|a, b| a + b`, after the
+    /// `fn`, `|` or `||` it starts with.
+    fn lambda(&mut self) -> Result<Expr<'src>> {
+        let start = self.previous();
+        let (params, return_type, body) = match start.r#type {
+            Fn => {
+                self.consume(&LeftParen, "expected '(' after 'fn'")?;
+                let params = self.parameters(&RightParen)?;
+                let return_type = self.return_type()?;
+                self.consume(&LeftBrace, "expected '{' before function body")?;
+                (params, return_type, self.block()?)
+            }
+            Pipe => (self.parameters(&Pipe)?, None, self.expression()?),
+            // `||` is a closure without parameters
+            _ => (Vec::new(), None, self.expression()?),
+        };
+        let span = start.span.to(body.span());
+        Ok(Expr::Lambda {
+            params,
+            return_type,
+            body: Box::new(body),
+            captures: Vec::new(),
+            span,
+        })
+    }
+
+    /// Parses the rest of an interpolated string after its [`InterpolationStart`].
+    fn interpolation(&mut self) -> Result<Expr<'src>> {
+        let mut fragment = self.previous();
+        let start = fragment.span;
+        let mut parts = Vec::new();
+        loop {
+            if let Some(Literal::String(text)) = fragment.literal {
+                if !text.is_empty() {
+                    parts.push(Part::Text(text));
+                }
+            }
+            if fragment.r#type == InterpolationEnd {
+                break;
+            }
+
+            if self.check(&InterpolationMiddle) || self.check(&InterpolationEnd) {
+                error_at(self.peek(), "expected expression in string interpolation");
+            } else {
+                parts.push(Part::Expr(Box::new(self.expression()?)));
+            }
+            fragment = if self.matches(&[InterpolationMiddle, InterpolationEnd]) {
+                self.previous()
+            } else {
+                error_at(self.peek(), "expected `}` after interpolated expression");
+                return Err(anyhow!("Parse error"));
+            };
+        }
+
+        let span = start.to(self.previous().span);
+        Ok(Expr::Interpolation(parts, span))
+    }
+
+    /// Parses the rest of a `a::b::c` path after its first segment.
+    fn path(&mut self, first: Token<'src>) -> Result<Expr<'src>> {
+        let mut segments = vec![first];
+        while self.matches(&[ColonColon]) {
+            let segment = self.consume(&Identifier, "expected identifier after `::`")?;
+            segments.push(segment);
+        }
+        let span = segments[0].span.to(segments[segments.len() - 1].span);
+        Ok(Expr::Path(segments, span))
+    }
+
+    /// Whether the next token can start an expression, used for the optional end of ranges.
+    fn can_begin_expression(&self) -> bool {
+        let r#type = &self.peek().r#type;
+        Operator::prefix_of(r#type).is_some()
+            || self.at_empty_closure()
+            || matches!(
+                r#type,
+                Identifier
+                    | Number
+                    | String
+                    | RawString
+                    | MultilineString
+                    | Char
+                    | InterpolationStart
+                    | Error
+                    | True
+                    | False
+                    | LeftParen
+                    | Fn
+                    | Pipe
+                    | Match
+                    | Loop
+                    | Label
+                    | LeftBracket
+            )
+    }
+
+    /// Whether the next token is the `||` of a closure without parameters. It lexes like
+    /// the `or` keyword, which can't start one.
+    fn at_empty_closure(&self) -> bool {
+        self.check(&Or) && self.peek().lexeme == "||"
+    }
+
     fn synchronize(&mut self) {
         self.advance();
         while !self.is_at_end() {
@@ -287,7 +1304,7 @@ impl Parser {
                 return;
             }
             match self.peek().r#type {
-                Let | For | If | While | Return => {
+                Let | Fn | Struct | Enum | For | If | While | Return | Break | Continue => {
                     return;
                 }
                 _ => {}
@@ -306,15 +1323,20 @@ impl Parser {
         false
     }
 
-    fn consume(&mut self, r#type: &TokenType, message: &str) -> Result<Token> {
+    fn consume(&mut self, r#type: &TokenType, message: &str) -> Result<Token<'src>> {
         if self.check(r#type) {
             Ok(self.advance())
         } else {
-            crate::error_at_token(&self.peek(), message);
+            error_at(self.peek(), message);
             Err(anyhow!("Parse error"))
         }
     }
 
+    /// Whether the token after the next one has the given type.
+    fn check_next(&mut self, r#type: &TokenType) -> bool {
+        &self.peek_nth(1).r#type == r#type
+    }
+
     fn check(&self, r#type: &TokenType) -> bool {
         if self.is_at_end() {
             false
@@ -323,9 +1345,19 @@ impl Parser {
         }
     }
 
-    fn advance(&mut self) -> Token {
+    fn advance(&mut self) -> Token<'src> {
         if !self.is_at_end() {
-            self.current += 1;
+            self.previous = self.lookahead.pop_front();
+            self.fill(1);
+            // reported once, when the token is consumed, not every time `advance` stops at
+            // the end
+            if let Some(error) = self
+                .previous
+                .as_ref()
+                .and_then(|token| token.error.as_ref())
+            {
+                crate::error_at(error.line, error.column, error.error.to_string());
+            }
         }
         self.previous()
     }
@@ -334,36 +1366,839 @@ impl Parser {
         self.peek().r#type == Eof
     }
 
-    fn peek(&self) -> Token {
-        self.tokens[self.current].clone()
+    fn peek(&self) -> &Token<'src> {
+        &self.lookahead[0]
     }
 
-    fn previous(&self) -> Token {
-        self.tokens[self.current - 1].clone()
+    /// Looks `n` tokens past the next one.
+    fn peek_nth(&mut self, n: usize) -> &Token<'src> {
+        self.fill(n + 1);
+        &self.lookahead[n]
+    }
+
+    fn previous(&self) -> Token<'src> {
+        self.previous
+            .clone()
+            .expect("a token is consumed before looking at the previous one")
+    }
+
+    /// Pulls tokens until there are at least `count` of them to look ahead at.
+    fn fill(&mut self, count: usize) {
+        while self.lookahead.len() < count {
+            let token = self.tokens.next().unwrap_or_else(|| {
+                // token sources that don't end with an `Eof` token get one anyway
+                let last = self.lookahead.back().or(self.previous.as_ref());
+                let end = last.map_or(0, |token| token.span.end);
+                let (line, column) = last.map_or((1, 1), |token| (token.line, token.column));
+                Token::new(Eof, "", None, Span::new(end, end), line, column)
+            });
+            self.lookahead.push_back(token);
+        }
     }
 }
 
+/// Reports an error at `token`, unless the lexer found a problem with it, which is
+/// reported when it's consumed instead.
+fn error_at(token: &Token, message: &str) {
+    if token.error.is_none() {
+        crate::error_at_token(token, message);
+    }
+}
+
+/// Makes a block of `statements`, whose value is the last of them if that's an expression
+/// without a semicolon after it.
+fn block<'src>(mut statements: Vec<Stmt<'src>>, span: Span) -> Expr<'src> {
+    let tail = match statements.pop() {
+        Some(Stmt::Expression(expr, stmt_span)) if stmt_span == expr.span() => Some(expr),
+        Some(stmt) => {
+            statements.push(stmt);
+            None
+        }
+        None => None,
+    };
+    Expr::Block(statements, tail.map(Box::new), span)
+}
+
+/// Whether an expression ends with a block, which ends a statement without a semicolon.
+const fn is_block_like(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::Block(..) | Expr::If { .. } | Expr::Loop { .. } | Expr::Match { .. }
+    )
+}
+
+fn is_inner_doc(token: &Token) -> bool {
+    token.lexeme.starts_with("//!")
+}
+
 #[cfg(test)]
 pub mod tests {
-    use crate::ast::{lexer::Lexer, Stmt};
+    use crate::ast::{
+        lexer::Lexer, Expr, ForClauses, Literal, Part, Pattern, Span, Stmt, Token, TokenType,
+        TypeExpr, VariantFields,
+    };
 
     use super::Parser;
 
     #[test]
     fn basic_parser_test() {
         let source = include_str!("../../test.blum");
-        let mut lexer = Lexer::new(source);
-        let mut parser = Parser::new(lexer.scan_tokens());
+        let mut parser = Parser::new(Lexer::new(source));
         let ast = parser.parse();
 
         match ast[0] {
-            Stmt::Fn(_, _, _) => (),
+            Stmt::Fn { .. } => (),
             _ => panic!(),
         }
 
         match ast[1] {
-            Stmt::Let(_, _) => (),
+            Stmt::Let { .. } => (),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn empty_sources() {
+        for source in ["", "  // nothing\n", "//! only module docs"] {
+            assert!(Parser::new(Lexer::new(source)).parse().is_empty());
+        }
+    }
+
+    #[test]
+    fn doc_comments_attach_to_declarations() {
+        let source = "//! module docs\n/* a /* nested */ comment */\n/// adds\n/// things\nfn add() {\n    let a = 1;\n}\n//// not a doc\nlet b = 2;";
+        let mut parser = Parser::new(Lexer::new(source));
+        let ast = parser.parse();
+
+        assert_eq!(parser.module_docs().len(), 1);
+        match &ast[0] {
+            Stmt::Fn { docs, .. } => {
+                let docs: Vec<_> = docs.iter().map(|doc| doc.literal.clone()).collect();
+                assert_eq!(
+                    docs,
+                    [
+                        Some(Literal::String(" adds".to_owned())),
+                        Some(Literal::String(" things".to_owned()))
+                    ]
+                );
+            }
+            _ => panic!(),
+        }
+        match &ast[1] {
+            Stmt::Let { docs, .. } => assert!(docs.is_empty()),
             _ => panic!(),
         }
+
+        // a stray doc comment at the end of a block leaves its `}` alone
+        let source = "fn f() { let a = 1;\n/// trailing\n}\nlet b = 2;";
+        let ast = Parser::new(Lexer::new(source)).parse();
+        assert!(matches!(ast[..], [Stmt::Fn { .. }, Stmt::Let { .. }]));
+    }
+
+    /// Renders an expression as an s-expression so tests can check its structure.
+    fn sexpr(expr: &Expr) -> String {
+        match expr {
+            Expr::Assign(name, value, _) => format!("(= {} {})", name.lexeme, sexpr(value)),
+            Expr::CompoundAssign(name, operator, value, _) => {
+                format!("({} {} {})", operator.lexeme, name.lexeme, sexpr(value))
+            }
+            Expr::CompoundIndexSet(object, index, operator, value, _) => format!(
+                "({} ([] {} {}) {})",
+                operator.lexeme,
+                sexpr(object),
+                sexpr(index),
+                sexpr(value)
+            ),
+            Expr::CompoundSet(object, name, operator, value, _) => format!(
+                "({} (. {} {}) {})",
+                operator.lexeme,
+                sexpr(object),
+                name.lexeme,
+                sexpr(value)
+            ),
+            Expr::Binary(left, operator, right, _) | Expr::Logical(left, operator, right, _) => {
+                format!("({} {} {})", operator.lexeme, sexpr(left), sexpr(right))
+            }
+            Expr::Block(statements, tail, _) => {
+                let tail = tail
+                    .as_ref()
+                    .map_or_else(|| "_".to_owned(), |tail| sexpr(tail));
+                format!("(block{} {tail})", ";".repeat(statements.len()))
+            }
+            Expr::Call { callee, args, .. } => {
+                let args: Vec<_> = args.iter().map(sexpr).collect();
+                format!("(call {} [{}])", sexpr(callee), args.join(" "))
+            }
+            Expr::Cast(expr, ty, _) => format!("(as {} {})", sexpr(expr), stype(ty)),
+            Expr::Array(elements, _) => {
+                let elements: Vec<_> = elements.iter().map(sexpr).collect();
+                format!("[{}]", elements.join(" "))
+            }
+            Expr::ArrayRepeat(value, count, _) => {
+                format!("[{}; {}]", sexpr(value), sexpr(count))
+            }
+            Expr::Get(object, name, _) => format!("(. {} {})", sexpr(object), name.lexeme),
+            Expr::Grouping(inner, _) => sexpr(inner),
+            Expr::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => match else_branch {
+                Some(else_branch) => format!(
+                    "(if {} {} {})",
+                    sexpr(condition),
+                    sexpr(then_branch),
+                    sexpr(else_branch)
+                ),
+                None => format!("(if {} {})", sexpr(condition), sexpr(then_branch)),
+            },
+            Expr::Index(object, index, _) => format!("([] {} {})", sexpr(object), sexpr(index)),
+            Expr::IndexSet(object, index, value, _) => format!(
+                "(= ([] {} {}) {})",
+                sexpr(object),
+                sexpr(index),
+                sexpr(value)
+            ),
+            Expr::Interpolation(parts, _) => {
+                let parts: Vec<_> = parts
+                    .iter()
+                    .map(|part| match part {
+                        Part::Text(text) => format!("{text:?}"),
+                        Part::Expr(expr) => sexpr(expr),
+                    })
+                    .collect();
+                format!("(interpolate {})", parts.join(" "))
+            }
+            Expr::Lambda { params, body, .. } => {
+                let params: Vec<_> = params.iter().map(|param| param.name.lexeme).collect();
+                format!("(fn [{}] {})", params.join(" "), sexpr(body))
+            }
+            Expr::Literal(Literal::Int(value, _), _) => value.to_string(),
+            Expr::Literal(literal, _) => format!("{literal:?}"),
+            Expr::Loop { label, body, .. } => match label {
+                Some(label) => format!("(loop {} {})", label.lexeme, sexpr(body)),
+                None => format!("(loop {})", sexpr(body)),
+            },
+            Expr::Match {
+                scrutinee, arms, ..
+            } => {
+                let arms: Vec<_> = arms
+                    .iter()
+                    .map(|arm| {
+                        let guard = arm
+                            .guard
+                            .as_ref()
+                            .map_or_else(String::new, |guard| format!(" if {}", sexpr(guard)));
+                        format!(" [{}{guard} {}]", spattern(&arm.pattern), sexpr(&arm.body))
+                    })
+                    .collect();
+                format!("(match {}{})", sexpr(scrutinee), arms.concat())
+            }
+            Expr::Path(segments, _) => segments
+                .iter()
+                .map(|segment| segment.lexeme)
+                .collect::<Vec<_>>()
+                .join("::"),
+            Expr::Range(start, operator, end, _) => format!(
+                "({} {} {})",
+                operator.lexeme,
+                start.as_deref().map_or_else(|| "_".to_owned(), sexpr),
+                end.as_deref().map_or_else(|| "_".to_owned(), sexpr)
+            ),
+            Expr::Set(object, name, value, _) => {
+                format!("(= (. {} {}) {})", sexpr(object), name.lexeme, sexpr(value))
+            }
+            Expr::StructLiteral(name, fields, _) => {
+                let fields: Vec<_> = fields
+                    .iter()
+                    .map(|(field, value)| format!(" [{} {}]", field.lexeme, sexpr(value)))
+                    .collect();
+                format!("(new {}{})", name.lexeme, fields.concat())
+            }
+            Expr::Try(inner, _) => format!("(? {})", sexpr(inner)),
+            Expr::Tuple(elements, _) => {
+                let elements: Vec<_> = elements.iter().map(sexpr).collect();
+                format!("(tuple {})", elements.join(" "))
+            }
+            Expr::Unary(operator, right, _) => format!("({} {})", operator.lexeme, sexpr(right)),
+            Expr::Variable(name, _) => name.lexeme.to_owned(),
+        }
+    }
+
+    fn stype(ty: &TypeExpr) -> String {
+        match ty {
+            TypeExpr::Named(name, args, _) if args.is_empty() => name.lexeme.to_owned(),
+            TypeExpr::Named(name, args, _) => {
+                let args: Vec<_> = args.iter().map(stype).collect();
+                format!("{}<{}>", name.lexeme, args.join(", "))
+            }
+            TypeExpr::Tuple(types, _) => {
+                let types: Vec<_> = types.iter().map(stype).collect();
+                format!("({})", types.join(", "))
+            }
+            TypeExpr::Array(element, _) => format!("[{}]", stype(element)),
+            TypeExpr::Fn(params, return_type, _) => {
+                let params: Vec<_> = params.iter().map(stype).collect();
+                let return_type = return_type
+                    .as_ref()
+                    .map_or_else(String::new, |ty| format!(" -> {}", stype(ty)));
+                format!("fn({}){return_type}", params.join(", "))
+            }
+            TypeExpr::Optional(inner, _) => format!("{}?", stype(inner)),
+        }
+    }
+
+    fn spattern(pattern: &Pattern) -> String {
+        let path = |path: &[Token]| {
+            path.iter()
+                .map(|segment| segment.lexeme)
+                .collect::<Vec<_>>()
+                .join("::")
+        };
+        match pattern {
+            Pattern::Literal(Literal::Int(value, _), _) => value.to_string(),
+            Pattern::Literal(literal, _) => format!("{literal:?}"),
+            Pattern::Negative(Literal::Int(value, _), _) => format!("-{value}"),
+            Pattern::Negative(literal, _) => format!("-{literal:?}"),
+            Pattern::Binding(name, _) => name.lexeme.to_owned(),
+            Pattern::Wildcard(_) => "_".to_owned(),
+            Pattern::Tuple(patterns, _) => {
+                let patterns: Vec<_> = patterns.iter().map(spattern).collect();
+                format!("(tuple {})", patterns.join(" "))
+            }
+            Pattern::Struct(name, fields, _) => {
+                let fields: Vec<_> = fields
+                    .iter()
+                    .map(|(field, pattern)| format!(" [{} {}]", field.lexeme, spattern(pattern)))
+                    .collect();
+                format!("({}{})", path(name), fields.concat())
+            }
+            Pattern::Variant(name, patterns, _) => {
+                let patterns: Vec<_> = patterns.iter().map(spattern).collect();
+                format!("({} {})", path(name), patterns.join(" "))
+            }
+        }
+    }
+
+    fn parse_expr(source: &str) -> String {
+        let source = format!("{source};");
+        let ast = Parser::new(Lexer::new(&source)).parse();
+        match &ast[..] {
+            [Stmt::Expression(expr, _)] => sexpr(expr),
+            _ => panic!("{source} did not parse as a single expression: {ast:?}"),
+        }
+    }
+
+    #[test]
+    fn function_parameters() {
+        let source = "fn f(a, b: i32, c: i32 = n + 1,) {\n    a;\n    return b;\n}";
+        let ast = Parser::new(Lexer::new(source)).parse();
+        let Stmt::Fn { params, body, .. } = &ast[0] else {
+            panic!()
+        };
+        let Expr::Block(body, None, _) = body.as_ref() else {
+            panic!()
+        };
+        assert!(matches!(body[..], [Stmt::Expression(..), Stmt::Return(..)]));
+
+        let params: Vec<_> = params
+            .iter()
+            .map(|param| {
+                let ty = param.ty.as_ref().map(stype);
+                let default = param.default.as_ref().map(sexpr);
+                (
+                    param.name.lexeme,
+                    ty,
+                    default,
+                    &source[param.span.start..param.span.end],
+                )
+            })
+            .collect();
+        assert_eq!(
+            params,
+            [
+                ("a", None, None, "a"),
+                ("b", Some("i32".to_owned()), None, "b: i32"),
+                (
+                    "c",
+                    Some("i32".to_owned()),
+                    Some("(+ n 1)".to_owned()),
+                    "c: i32 = n + 1"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn operator_precedence() {
+        assert_eq!(
+            parse_expr("x += 1 + 2 * 3 % 4 << 1 & 7 | 8 ^ 2 == a && b || ~c"),
+            "(+= x (|| (&& (== (| (& (<< (+ 1 (% (* 2 3) 4)) 1) 7) (^ 8 2)) a) b) (~ c)))"
+        );
+        assert_eq!(parse_expr("a and b or c"), "(or (and a b) c)");
+        assert_eq!(parse_expr("0..n - 1"), "(.. 0 (- n 1))");
+        assert_eq!(parse_expr("..=b"), "(..= _ b)");
+        assert_eq!(parse_expr("-a::b?"), "(- (? a::b))");
+    }
+
+    #[test]
+    fn precedence_pairs() {
+        // one infix operator of every level, from the loosest to the tightest
+        let levels = ["..", "||", "&&", "==", "<", "|", "^", "&", "<<", "+", "*"];
+        for (i, looser) in levels.iter().enumerate() {
+            for tighter in &levels[i + 1..] {
+                assert_eq!(
+                    parse_expr(&format!("a {looser} b {tighter} c")),
+                    format!("({looser} a ({tighter} b c))")
+                );
+                assert_eq!(
+                    parse_expr(&format!("a {tighter} b {looser} c")),
+                    format!("({looser} ({tighter} a b) c)")
+                );
+            }
+            assert_eq!(
+                parse_expr(&format!("x = a {looser} b")),
+                format!("(= x ({looser} a b))")
+            );
+            assert_eq!(
+                parse_expr(&format!("-a {looser} b as T")),
+                format!("({looser} (- a) (as b T))")
+            );
+            assert_eq!(
+                parse_expr(&format!("a? {looser} ~b(c)")),
+                format!("({looser} (? a) (~ (call b [c])))")
+            );
+        }
+
+        assert_eq!(parse_expr("-a as T"), "(as (- a) T)");
+        assert_eq!(parse_expr("f(a)? as T"), "(as (? (call f [a])) T)");
+        assert_eq!(parse_expr("..a + b"), "(.. _ (+ a b))");
+        assert_eq!(parse_expr("a..b || c"), "(.. a (|| b c))");
+    }
+
+    #[test]
+    fn associativity() {
+        assert_eq!(parse_expr("a - b - c"), "(- (- a b) c)");
+        assert_eq!(parse_expr("a || b || c"), "(|| (|| a b) c)");
+        assert_eq!(parse_expr("a = b += c"), "(= a (+= b c))");
+        assert_eq!(parse_expr("a as T as U"), "(as (as a T) U)");
+        assert_eq!(parse_expr("f() as i32?"), "(? (as (call f []) i32))");
+        assert_eq!(parse_expr("f() as (i32?)"), "(as (call f []) i32?)");
+        assert_eq!(parse_expr("!-a"), "(! (- a))");
+        assert_eq!(parse_expr("a?()?"), "(? (call (? a) []))");
+    }
+
+    #[test]
+    fn lambdas() {
+        assert_eq!(
+            parse_expr("fn(a, b) { a + b }"),
+            "(fn [a b] (block (+ a b)))"
+        );
+        assert_eq!(
+            parse_expr("fn() { let c = 1; f(c); }"),
+            "(fn [] (block;; _))"
+        );
+        assert_eq!(parse_expr("|a, b: i32| a + b"), "(fn [a b] (+ a b))");
+        assert_eq!(parse_expr("|| |x| x(1)"), "(fn [] (fn [x] (call x [1])))");
+        assert_eq!(parse_expr("a || b"), "(|| a b)");
+        assert!(Parser::new(Lexer::new("let f = or 1;")).parse().is_empty());
+
+        let ast = Parser::new(Lexer::new(
+            "fn(x) { x }(1);
+fn named() {}",
+        ))
+        .parse();
+        assert!(matches!(ast[..], [Stmt::Expression(..), Stmt::Fn { .. }]));
+    }
+
+    #[test]
+    fn call_expressions() {
+        assert_eq!(parse_expr("f()"), "(call f [])");
+        assert_eq!(
+            parse_expr("f(1)(a, b + 2,)"),
+            "(call (call f [1]) [a (+ b 2)])"
+        );
+        assert_eq!(parse_expr("-a::new(x)?"), "(- (? (call a::new [x])))");
+
+        let Expr::Call { paren, span, .. } =
+            Parser::new(Lexer::new("f(1, 2)")).expression().unwrap()
+        else {
+            panic!()
+        };
+        assert_eq!((paren.lexeme, paren.span.start), (")", 6));
+        assert_eq!(span, Span::new(0, 7));
+    }
+
+    #[test]
+    fn string_interpolation() {
+        assert_eq!(
+            parse_expr(r#""hello {name}, {age + 1}""#),
+            r#"(interpolate "hello " name ", " (+ age 1))"#
+        );
+        assert_eq!(
+            parse_expr(r#""{"{a}"}!""#),
+            r#"(interpolate (interpolate a) "!")"#
+        );
+    }
+
+    #[test]
+    fn structs_and_fields() {
+        let source = "struct Point { x: f64, y: f64, }
+struct Unit {}";
+        let ast = Parser::new(Lexer::new(source)).parse();
+        let Stmt::Struct { name, fields, .. } = &ast[0] else {
+            panic!()
+        };
+        let fields: Vec<_> = fields
+            .iter()
+            .map(|field| format!("{}: {}", field.name.lexeme, stype(&field.ty)))
+            .collect();
+        assert_eq!(name.lexeme, "Point");
+        assert_eq!(fields, ["x: f64", "y: f64"]);
+        assert!(matches!(&ast[1], Stmt::Struct { fields, .. } if fields.is_empty()));
+
+        assert_eq!(parse_expr("Point { x: 1, y }"), "(new Point [x 1] [y y])");
+        assert_eq!(parse_expr("Unit {}"), "(new Unit)");
+        assert_eq!(parse_expr("a.b.c(1)"), "(call (. (. a b) c) [1])");
+        assert_eq!(parse_expr("p.x = q.y = 3"), "(= (. p x) (= (. q y) 3))");
+        assert_eq!(parse_expr("p.x += q.y -= 1"), "(+= (. p x) (-= (. q y) 1))");
+        assert_eq!(parse_expr("-p.x"), "(- (. p x))");
+    }
+
+    #[test]
+    fn enums_and_match() {
+        let source = "enum Shape { Circle(f64), Rect { w: f64, h: f64 }, Empty, }";
+        let ast = Parser::new(Lexer::new(source)).parse();
+        let Stmt::Enum { name, variants, .. } = &ast[0] else {
+            panic!()
+        };
+        assert_eq!(name.lexeme, "Shape");
+        assert!(matches!(
+            &variants[..],
+            [a, b, c] if matches!(&a.fields, VariantFields::Tuple(types) if types.len() == 1)
+                && matches!(&b.fields, VariantFields::Struct(fields) if fields.len() == 2)
+                && c.fields == VariantFields::Unit
+        ));
+
+        assert_eq!(
+            parse_expr("match shape { Shape::Circle(r) if r > 0 => r, Shape::Rect { w, h: 1 } => w, Shape::Empty => { 0 } _ => 1 }"),
+            "(match shape [(Shape::Circle r) if (> r 0) r] [(Shape::Rect [w w] [h 1]) w] [(Shape::Empty ) (block 0)] [_ 1])"
+        );
+        assert_eq!(
+            parse_expr("match pair { (1, (x)) => x, (_, y,) => y, }"),
+            "(match pair [(tuple 1 x) x] [(tuple _ y) y])"
+        );
+        assert_eq!(
+            parse_expr("match n { -1 => 0, (-2, -0.5) => 1, _ => 2 }"),
+            "(match n [-1 0] [(tuple -2 -Float(0.5, None)) 1] [_ 2])"
+        );
+        // the block after the scrutinee holds the arms, not struct literal fields
+        assert_eq!(
+            parse_expr("match p { x => Point { x } }"),
+            "(match p [x (new Point [x x])])"
+        );
+    }
+
+    #[test]
+    fn arrays_and_indexing() {
+        assert_eq!(parse_expr("[]"), "[]");
+        assert_eq!(parse_expr("[1, a + 2, [3],]"), "[1 (+ a 2) [3]]");
+        assert_eq!(parse_expr("[0; n * 2]"), "[0; (* n 2)]");
+        assert_eq!(parse_expr("a[i][j + 1]"), "([] ([] a i) (+ j 1))");
+        assert_eq!(parse_expr("a[1..3]"), "([] a (.. 1 3))");
+        assert_eq!(parse_expr("a[..]"), "([] a (.. _ _))");
+        assert_eq!(parse_expr("p.xs[i] = v"), "(= ([] (. p xs) i) v)");
+        assert_eq!(
+            parse_expr("a[0] += a[i] *= 2"),
+            "(+= ([] a 0) (*= ([] a i) 2))"
+        );
+        assert_eq!(parse_expr("-a[0].b"), "(- (. ([] a 0) b))");
+    }
+
+    #[test]
+    fn tuples_and_destructuring() {
+        assert_eq!(parse_expr("()"), "(tuple )");
+        assert_eq!(parse_expr("(a)"), "a");
+        assert_eq!(parse_expr("(a,)"), "(tuple a)");
+        assert_eq!(
+            parse_expr("(a, (b, c), 1 + 2,)"),
+            "(tuple a (tuple b c) (+ 1 2))"
+        );
+        assert_eq!(
+            parse_expr("x as (i32, (f64,), ())"),
+            "(as x (i32, (f64), ()))"
+        );
+
+        let source = "let (x, (y, _)) = pair;\nlet Point { x, y: (a, b) } = p;\nlet n;";
+        let ast = Parser::new(Lexer::new(source)).parse();
+        let patterns: Vec<_> = ast
+            .iter()
+            .map(|stmt| match stmt {
+                Stmt::Let { pattern, .. } => spattern(pattern),
+                _ => panic!(),
+            })
+            .collect();
+        assert_eq!(
+            patterns,
+            [
+                "(tuple x (tuple y _))",
+                "(Point [x x] [y (tuple a b)])",
+                "n"
+            ]
+        );
+    }
+
+    #[test]
+    fn type_annotations() {
+        let source = "
+            let a: Vec<Vec<i32>> = v;
+            let (b, c): (fn(i32, [f64]) -> bool?, fn());
+            fn f(g: Map<str, [i32]?>?) -> (i32,) { return g; }
+            struct S { h: fn() -> fn() -> i32, }
+            let d: Vec<i32>= w;
+            let e: Vec<Vec<i32>>= w;
+        ";
+        let ast = Parser::new(Lexer::new(source)).parse();
+        let types: Vec<_> = ast
+            .iter()
+            .flat_map(|stmt| match stmt {
+                Stmt::Let { ty, .. } => vec![ty.as_ref().map(stype)],
+                Stmt::Fn {
+                    params,
+                    return_type,
+                    ..
+                } => vec![
+                    params[0].ty.as_ref().map(stype),
+                    return_type.as_ref().map(stype),
+                ],
+                Stmt::Struct { fields, .. } => vec![Some(stype(&fields[0].ty))],
+                _ => panic!(),
+            })
+            .collect();
+        assert_eq!(
+            types,
+            [
+                "Vec<Vec<i32>>",
+                "(fn(i32, [f64]) -> bool?, fn())",
+                "Map<str, [i32]?>?",
+                "(i32)",
+                "fn() -> fn() -> i32",
+                "Vec<i32>",
+                "Vec<Vec<i32>>",
+            ]
+            .map(|ty| Some(ty.to_owned()))
+        );
+        assert_eq!(parse_expr("fn(a: i32) -> i32 { a }"), "(fn [a] (block a))");
+    }
+
+    #[test]
+    fn loops_and_labels() {
+        let source = "
+            for (let i = 0; i < 10; i += 1) { continue; }
+            'outer: while (true) { 'inner: for (;;) { break 'outer; } }
+            'a: loop { loop { continue 'a; } }
+        ";
+        let ast = Parser::new(Lexer::new(source)).parse();
+        let [Stmt::For {
+            label: None,
+            clauses,
+            body: for_body,
+            ..
+        }, Stmt::While {
+            label: Some(outer),
+            body,
+            ..
+        }, Stmt::Expression(labeled, _)] = &ast[..]
+        else {
+            panic!("{ast:?}")
+        };
+        let ForClauses::CStyle {
+            initializer: Some(initializer),
+            condition: Some(condition),
+            increment: Some(increment),
+        } = clauses.as_ref()
+        else {
+            panic!()
+        };
+        assert!(matches!(initializer.as_ref(), Stmt::Let { .. }));
+        assert_eq!(
+            (sexpr(condition), sexpr(increment)),
+            ("(< i 10)".to_owned(), "(+= i 1)".to_owned())
+        );
+        assert!(
+            matches!(for_body.as_ref(), Stmt::Expression(Expr::Block(body, None, _), _) if matches!(body[..], [Stmt::Continue(_, None, _)]))
+        );
+        assert_eq!(outer.lexeme, "'outer");
+        let Stmt::Expression(Expr::Block(body, None, _), _) = body.as_ref() else {
+            panic!()
+        };
+        assert!(matches!(
+            &body[..],
+            [Stmt::For { label: Some(inner), .. }] if inner.lexeme == "'inner"
+        ));
+        assert_eq!(sexpr(labeled), "(loop 'a (block (loop (block; _))))");
+
+        assert_eq!(
+            parse_expr("loop { if (x) { break x; } }"),
+            "(loop (block (if x (block; _))))"
+        );
+    }
+
+    #[test]
+    fn if_expressions_and_blocks() {
+        assert_eq!(
+            parse_expr("(if a > b { a } else { b })"),
+            "(if (> a b) (block a) (block b))"
+        );
+        assert_eq!(
+            parse_expr("(if (a) { 1 } else if b { 2 })"),
+            "(if a (block 1) (if b (block 2)))"
+        );
+        assert_eq!(parse_expr("({ let y = 1; { y } })"), "(block; (block y))");
+        // parentheses that start the condition don't have to be around all of it
+        assert_eq!(
+            parse_expr("(if (a || b) && c {})"),
+            "(if (&& (|| a b) c) (block _))"
+        );
+        assert_eq!(parse_expr("(if (a) == b {})"), "(if (== a b) (block _))");
+        assert_eq!(
+            parse_expr("(if (a).len() > 0 {})"),
+            "(if (> (call (. a len) []) 0) (block _))"
+        );
+        assert_eq!(parse_expr("(if (x) - y {})"), "(if (- x y) (block _))");
+        // the block after the condition is the branch, not a struct literal
+        assert_eq!(
+            parse_expr("(if p == origin {} else {})"),
+            "(if (== p origin) (block _) (block _))"
+        );
+        assert_eq!(
+            parse_expr("|x| { if x { f(); } g() }"),
+            "(fn [x] (block; (call g [])))"
+        );
+
+        let source = "
+            let x = if a > b { a } else { b };
+            let y = if (a || b) && c { 1 } else { 2 };
+            if done { return; } else if (x) print(x); else { x += 1; }
+            if (done) return;
+        ";
+        let ast = Parser::new(Lexer::new(source)).parse();
+        let [Stmt::Let {
+            initializer: Some(Expr::If { .. }),
+            ..
+        }, Stmt::Let {
+            initializer: Some(y),
+            ..
+        }, Stmt::Expression(chain, _), Stmt::Expression(last, _)] = &ast[..]
+        else {
+            panic!("{ast:?}")
+        };
+        assert_eq!(sexpr(y), "(if (&& (|| a b) c) (block 1) (block 2))");
+        assert_eq!(
+            sexpr(chain),
+            "(if done (block; _) (if x (block; _) (block; _)))"
+        );
+        assert_eq!(sexpr(last), "(if done (block; _))");
+
+        // blocks end with their value wherever they are, not just in expressions
+        let source = "
+            fn f() -> i32 { if a > b { a } else { b } }
+            if x { 1 } else { 2 }
+            { 1 }
+        ";
+        let ast = Parser::new(Lexer::new(source)).parse();
+        let [Stmt::Fn { body, .. }, Stmt::Expression(if_else, _), Stmt::Expression(block, _)] =
+            &ast[..]
+        else {
+            panic!("{ast:?}")
+        };
+        assert_eq!(sexpr(body), "(block (if (> a b) (block a) (block b)))");
+        assert_eq!(sexpr(if_else), "(if x (block 1) (block 2))");
+        assert_eq!(sexpr(block), "(block 1)");
+    }
+
+    #[test]
+    fn statement_branches() {
+        // a condition in parentheses ends at its `)` when a statement follows
+        let source = "
+            fn g() { if (x) !y; else -z; }
+            let h = || { if (x) !y; else -z; };
+        ";
+        let ast = Parser::new(Lexer::new(source)).parse();
+        let [Stmt::Fn { body: function, .. }, Stmt::Let {
+            initializer: Some(Expr::Lambda { body: closure, .. }),
+            ..
+        }] = &ast[..]
+        else {
+            panic!("{ast:?}")
+        };
+        for body in [function, closure] {
+            let Expr::Block(_, Some(tail), _) = body.as_ref() else {
+                panic!("{body:?}")
+            };
+            let Expr::If {
+                then_branch,
+                else_branch: Some(else_branch),
+                ..
+            } = tail.as_ref()
+            else {
+                panic!("{tail:?}")
+            };
+            for (branch, expected) in [(then_branch, "(! y)"), (else_branch, "(- z)")] {
+                assert!(matches!(
+                    branch.as_ref(),
+                    Expr::Block(statements, None, _)
+                        if matches!(&statements[..], [Stmt::Expression(expr, _)] if sexpr(expr) == expected)
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn for_in_loops() {
+        let source = "
+            for x in 0..n { f(x); }
+            for (a, (b, _)) in pairs {}
+            for (; i < 3;) {}
+            for p in points { p.x = 0; }
+        ";
+        let ast = Parser::new(Lexer::new(source)).parse();
+        let clauses: Vec<_> = ast
+            .iter()
+            .map(|stmt| match stmt {
+                Stmt::For { clauses, .. } => match clauses.as_ref() {
+                    ForClauses::In { pattern, iterable } => {
+                        format!("{} in {}", spattern(pattern), sexpr(iterable))
+                    }
+                    ForClauses::CStyle { condition, .. } => {
+                        format!(
+                            "while {}",
+                            condition.as_ref().map(sexpr).unwrap_or_default()
+                        )
+                    }
+                },
+                _ => panic!("{stmt:?}"),
+            })
+            .collect();
+        assert_eq!(
+            clauses,
+            [
+                "x in (.. 0 n)",
+                "(tuple a (tuple b _)) in pairs",
+                "while (< i 3)",
+                "p in points",
+            ]
+        );
+
+        // telling the two apart doesn't look past the header, even when it's unbalanced
+        for (header, c_style) in [("for ((a, b in xs", false), ("for ((i = 0;", true)] {
+            let tokens = Lexer::new(header)
+                .take_while(|token| token.r#type != TokenType::Eof)
+                .chain(std::iter::from_fn(|| panic!("looked past `{header}`")));
+            let mut parser = Parser::new(tokens);
+            parser.advance();
+            assert_eq!(parser.at_c_style_for(), c_style);
+        }
     }
 }
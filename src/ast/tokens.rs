@@ -1,4 +1,7 @@
+use super::Span;
+use std::borrow::Cow;
 use std::fmt;
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization as _};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
@@ -7,71 +10,336 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
+    DotDot,
+    DotDotEqual,
+    Colon,
+    ColonColon,
     Minus,
+    MinusEqual,
     Plus,
+    PlusEqual,
     Semicolon,
     Slash,
+    SlashEqual,
     Star,
+    StarEqual,
+    Percent,
+    PercentEqual,
+    Amp,
+    Pipe,
+    Caret,
+    Tilde,
+    Question,
+    Arrow,
+    FatArrow,
     Bang,
     BangEqual,
     Equal,
     EqualEqual,
     Greater,
     GreaterEqual,
+    GreaterGreater,
     Less,
     LessEqual,
+    LessLess,
     Identifier,
+    DocComment,
     String,
+    InterpolationStart,
+    InterpolationMiddle,
+    InterpolationEnd,
+    RawString,
+    MultilineString,
+    Char,
+    /// A loop label like `'outer`.
+    Label,
     Number,
     And,
+    As,
+    Break,
+    Continue,
     Else,
+    Enum,
     False,
     Fn,
     For,
     If,
+    In,
+    Loop,
+    Match,
     Or,
     Return,
+    Struct,
     True,
     Let,
     While,
+    /// Text that doesn't start any token, like an unexpected character, with the reason
+    /// in [`Token::error`].
+    Error,
     Eof,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 #[non_exhaustive]
-pub struct Token {
+pub struct Token<'src> {
     pub r#type: TokenType,
-    pub lexeme: String,
+    pub lexeme: &'src str,
     pub literal: Option<Literal>,
+    pub span: Span,
     pub line: usize,
+    pub column: usize,
+    /// Whitespace and comments before the token, only recorded by a lossless [`Lexer`].
+    ///
+    /// [`Lexer`]: super::lexer::Lexer
+    pub leading_trivia: Vec<Trivia<'src>>,
+    /// Whitespace and comments after the token up to the end of its line.
+    pub trailing_trivia: Vec<Trivia<'src>>,
+    /// The first problem the lexer found in the token. A malformed literal keeps its
+    /// type, so an error in one part of an interpolated string doesn't lose track of it.
+    pub error: Option<Box<TokenError>>,
 }
 
-impl Token {
+impl<'src> Token<'src> {
     #[inline]
     #[must_use]
-    pub fn new(r#type: TokenType, lexeme: &str, literal: Option<Literal>, line: usize) -> Self {
+    pub fn new(
+        r#type: TokenType,
+        lexeme: &'src str,
+        literal: Option<Literal>,
+        span: Span,
+        line: usize,
+        column: usize,
+    ) -> Self {
         Self {
             r#type,
-            lexeme: lexeme.to_owned(),
+            lexeme,
             literal,
+            span,
             line,
+            column,
+            leading_trivia: Vec::new(),
+            trailing_trivia: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// The lexeme in Unicode normalization form C, so that identifiers made of different
+    /// but canonically equivalent code points, like a precomposed `ö` and an `o` followed
+    /// by a combining diaeresis, compare equal.
+    #[must_use]
+    pub fn name(&self) -> Cow<'src, str> {
+        if is_nfc_quick(self.lexeme.chars()) == IsNormalized::Yes {
+            Cow::Borrowed(self.lexeme)
+        } else {
+            Cow::Owned(self.lexeme.nfc().collect())
+        }
+    }
+
+    /// Writes the token exactly as it appeared in the source, including its trivia.
+    pub fn write_source(&self, out: &mut String) {
+        for trivia in &self.leading_trivia {
+            out.push_str(trivia.text);
+        }
+        out.push_str(self.lexeme);
+        for trivia in &self.trailing_trivia {
+            out.push_str(trivia.text);
         }
     }
 }
 
-impl fmt::Display for Token {
+impl fmt::Display for Token<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?} {} {:?}", self.r#type, self.lexeme, self.literal)
     }
 }
 
+/// Source text between tokens that doesn't affect the meaning of the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trivia<'src> {
+    pub kind: TriviaKind,
+    pub text: &'src str,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TriviaKind {
+    Whitespace,
+    Newline,
+    LineComment,
+    BlockComment,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 #[non_exhaustive]
 pub enum Literal {
     String(String),
-    Number(f64),
+    Char(char),
+    Int(u128, Option<NumberSuffix>),
+    Float(f64, Option<NumberSuffix>),
     Bool(bool),
     Nil,
 }
+
+/// Something the lexer found suspicious about a token that's still valid, like an
+/// identifier that looks like another one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexWarning {
+    pub message: String,
+    pub span: Span,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A problem the lexer found in a token, and where it is, which for a bad escape
+/// sequence is its `\\` rather than the start of the string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenError {
+    pub error: LexError,
+    pub span: Span,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A problem the lexer found in a token.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum LexError {
+    #[error("unexpected character `{}`", .0.escape_debug())]
+    UnexpectedCharacter(char),
+    #[error("unterminated block comment")]
+    UnterminatedBlockComment,
+    #[error("unterminated string")]
+    UnterminatedString,
+    #[error("multiline strings must start on a new line after `\"\"\"`")]
+    MultilineStringStart,
+    #[error("unterminated multiline string")]
+    UnterminatedMultilineString,
+    #[error("unterminated raw string")]
+    UnterminatedRawString,
+    #[error("empty character literal")]
+    EmptyChar,
+    #[error("unterminated character literal")]
+    UnterminatedChar,
+    #[error("character literals may only contain one character, use a string instead")]
+    MultipleChars,
+    #[error("unterminated escape sequence")]
+    UnterminatedEscape,
+    #[error("unknown escape sequence `\\{0}`")]
+    UnknownEscape(char),
+    #[error("unknown escape sequence `\\` at end of line")]
+    EscapeAtEndOfLine,
+    #[error("expected `{{` after `\\u`")]
+    MissingUnicodeBrace,
+    #[error("unterminated unicode escape, expected `}}`")]
+    UnterminatedUnicodeEscape,
+    #[error("invalid character `{0}` in unicode escape")]
+    InvalidUnicodeDigit(char),
+    #[error("empty unicode escape, expected hex digits")]
+    EmptyUnicodeEscape,
+    #[error("unicode escape must have at most 6 hex digits")]
+    LongUnicodeEscape,
+    #[error("invalid unicode escape `\\u{{{0}}}`, not a unicode scalar value")]
+    InvalidUnicodeScalar(String),
+    #[error("missing digits after the {0} prefix")]
+    MissingDigits(&'static str),
+    #[error("invalid digit `{0}` in {1} literal")]
+    InvalidDigit(char, &'static str),
+    #[error("{0} float literals are not supported")]
+    RadixFloat(&'static str),
+    #[error("invalid suffix `{0}` for number literal")]
+    InvalidSuffix(String),
+    #[error("integer literal is out of range for `{0}`")]
+    IntOutOfRange(NumberSuffix),
+    #[error("float literals cannot have the integer suffix `{0}`")]
+    IntSuffixOnFloat(NumberSuffix),
+    #[error("float literal is out of range for `{0}`")]
+    FloatOutOfRange(NumberSuffix),
+}
+
+/// A type suffix on a numeric literal, such as the `u8` in `10u8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NumberSuffix {
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    F32,
+    F64,
+}
+
+impl NumberSuffix {
+    #[must_use]
+    pub fn parse(suffix: &str) -> Option<Self> {
+        Some(match suffix {
+            "i8" => Self::I8,
+            "i16" => Self::I16,
+            "i32" => Self::I32,
+            "i64" => Self::I64,
+            "i128" => Self::I128,
+            "u8" => Self::U8,
+            "u16" => Self::U16,
+            "u32" => Self::U32,
+            "u64" => Self::U64,
+            "u128" => Self::U128,
+            "f32" => Self::F32,
+            "f64" => Self::F64,
+            _ => return None,
+        })
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_float(self) -> bool {
+        matches!(self, Self::F32 | Self::F64)
+    }
+
+    /// The largest integer literal of this type, `None` for float types.
+    #[must_use]
+    pub const fn int_max(self) -> Option<u128> {
+        Some(match self {
+            Self::I8 => i8::MAX as u128,
+            Self::I16 => i16::MAX as u128,
+            Self::I32 => i32::MAX as u128,
+            Self::I64 => i64::MAX as u128,
+            Self::I128 => i128::MAX as u128,
+            Self::U8 => u8::MAX as u128,
+            Self::U16 => u16::MAX as u128,
+            Self::U32 => u32::MAX as u128,
+            Self::U64 => u64::MAX as u128,
+            Self::U128 => u128::MAX,
+            Self::F32 | Self::F64 => return None,
+        })
+    }
+}
+
+impl fmt::Display for NumberSuffix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::I8 => "i8",
+            Self::I16 => "i16",
+            Self::I32 => "i32",
+            Self::I64 => "i64",
+            Self::I128 => "i128",
+            Self::U8 => "u8",
+            Self::U16 => "u16",
+            Self::U32 => "u32",
+            Self::U64 => "u64",
+            Self::U128 => "u128",
+            Self::F32 => "f32",
+            Self::F64 => "f64",
+        };
+        f.write_str(name)
+    }
+}
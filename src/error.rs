@@ -22,11 +22,30 @@ impl Handler {
     }
 
     pub fn error<T: Into<String>>(pos: usize, message: T) {
+        Self::report(&pos.to_string(), message.into());
+    }
+
+    pub fn error_at<T: Into<String>>(line: usize, column: usize, message: T) {
+        Self::report(&format!("{line}:{column}"), message.into());
+    }
+
+    pub fn warning_at<T: Into<String>>(line: usize, column: usize, message: T) {
         let message: String = message.into();
 
         match HANDLER_LOCK.get() {
             Some(handle) => {
-                println!("{}:{pos} error: {message}", handle.source_file);
+                println!("{}:{line}:{column} warning: {message}", handle.source_file);
+            }
+            None => {
+                println!("blum: warning: {message}");
+            }
+        }
+    }
+
+    fn report(location: &str, message: String) {
+        match HANDLER_LOCK.get() {
+            Some(handle) => {
+                println!("{}:{location} error: {message}", handle.source_file);
                 handle.error_counter.set(handle.error_counter.get() + 1);
 
                 if handle.error_counter.get() == 20 {
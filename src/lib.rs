@@ -0,0 +1,20 @@
+pub mod ast;
+pub mod error;
+
+use error::Handler;
+
+pub fn error(pos: usize, message: impl Into<String>) {
+    Handler::error(pos, message);
+}
+
+pub fn error_at(line: usize, column: usize, message: impl Into<String>) {
+    Handler::error_at(line, column, message);
+}
+
+pub fn warning_at(line: usize, column: usize, message: impl Into<String>) {
+    Handler::warning_at(line, column, message);
+}
+
+pub fn error_at_token(token: &ast::Token, message: impl Into<String>) {
+    error_at(token.line, token.column, message);
+}
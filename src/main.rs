@@ -1,11 +1,7 @@
-use ast::{lexer::Lexer, parser::Parser};
+use blum::ast::{captures, checker, lexer::Lexer, lower, parser::Parser};
+use blum::error::Handler;
 use std::{env::args, fs, process::exit};
 
-pub mod ast;
-pub mod error;
-
-use error::Handler;
-
 fn main() {
     let path = args().nth(1);
 
@@ -15,7 +11,7 @@ fn main() {
 
             let file_contents = fs::read_to_string(path.clone())
                 .inspect_err(|err| {
-                    crate::error(
+                    blum::error(
                         1,
                         format!("error opening the file at `{path}`, error: {err}"),
                     );
@@ -23,27 +19,22 @@ fn main() {
                 .unwrap();
 
             let mut lexer = Lexer::new(&file_contents);
-            let tokens = lexer.scan_tokens();
-            //println!("{tokens:#?}");
-            let mut parser = Parser::new(tokens);
-            let ast = parser.parse();
+            let mut ast = Parser::new(&mut lexer).parse();
+            for warning in lexer.warnings() {
+                blum::warning_at(warning.line, warning.column, &warning.message);
+            }
+            for (token, error) in checker::check(&ast) {
+                blum::error_at_token(&token, error.to_string());
+            }
 
             if Handler::errors_occured() {
                 exit(10);
             }
+            captures::analyze(&mut ast);
+            lower::lower(&mut ast);
 
             println!("{ast:#?}");
         }
-        None => crate::error(1, "no source file given"),
+        None => blum::error(1, "no source file given"),
     }
 }
-
-fn error(pos: usize, message: impl Into<String>) {
-    Handler::error(pos, message);
-}
-
-fn error_at_token(token: &ast::Token, message: impl Into<String>) {
-    let message: String = message.into();
-    let pos = token.line;
-    error(pos, message);
-}